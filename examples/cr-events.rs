@@ -5,7 +5,7 @@ use std::time::Instant;
 use clap::{App, Arg, ArgMatches};
 use colored::*;
 
-use microvmi::api::events::{CrType, EventType, InterceptType};
+use microvmi::api::events::{CrType, EventReplyType, EventType, InterceptType};
 use microvmi::api::params::DriverInitParams;
 use microvmi::api::Introspectable;
 use utilities::Clappable;
@@ -111,8 +111,8 @@ fn main() {
                     "[{}] {} - {}:    old value: 0x{:x}    new value: 0x{:x}",
                     ev_nb_output, vcpu_output, cr_output, old, new
                 );
-                // drv.reply_event(ev, EventReplyType::Continue)
-                //   .expect("Failed to send event reply");
+                drv.reply_event(ev, EventReplyType::Continue)
+                    .expect("Failed to send event reply");
                 i += 1;
             }
             None => println!("No events yet..."),
@@ -69,6 +69,9 @@ fn main() {
     }
 
     let max_addr = drv.get_max_physical_addr().unwrap();
+    let memory_map = drv
+        .get_memory_map()
+        .expect("Failed to get the physical memory map");
     println!(
         "Dumping physical memory to {} until {:#X}",
         dump_path.file_name().unwrap().to_str().unwrap(),
@@ -82,7 +85,12 @@ fn main() {
     // redraw every 0.1% change, otherwise it becomes the bottleneck
     bar.set_draw_delta(max_addr / 1000);
 
+    // the memory map is sorted, walk it along with the pages
+    let mut ranges = memory_map.iter().peekable();
     for cur_addr in (0..max_addr).step_by(PAGE_SIZE) {
+        while ranges.peek().map_or(false, |range| range.end <= cur_addr) {
+            ranges.next();
+        }
         trace!(
             "reading {:#X} bytes of memory at {:#X}",
             PAGE_SIZE,
@@ -90,9 +98,15 @@ fn main() {
         );
         // reset buffer each loop
         let mut buffer: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
-        let mut _bytes_read = 0;
-        drv.read_physical(cur_addr, &mut buffer, &mut _bytes_read)
-            .unwrap_or_else(|_| debug!("failed to read memory at {:#X}", cur_addr));
+        // holes are padded with zeroes, to keep the dump offsets equal to the physical addresses
+        if ranges
+            .peek()
+            .map_or(false, |range| range.contains(cur_addr))
+        {
+            let mut _bytes_read = 0;
+            drv.read_physical(cur_addr, &mut buffer, &mut _bytes_read)
+                .unwrap_or_else(|_| debug!("failed to read memory at {:#X}", cur_addr));
+        }
         dump_file
             .write_all(&buffer)
             .expect("failed to write to file");
@@ -1,3 +1,10 @@
 from microvmi.microvmi import DriverType, Microvmi
 
-from .pymicrovmi import CommonInitParamsPy, DriverInitParamsPy, KVMInitParamsPy, MemflowInitParamsPy
+from .pymicrovmi import (
+    CommonInitParamsPy,
+    DriverInitParamsPy,
+    FileInitParamsPy,
+    KVMInitParamsPy,
+    MemflowInitParamsPy,
+    MockInitParamsPy,
+)
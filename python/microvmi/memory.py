@@ -1,9 +1,10 @@
 import logging
 from array import ArrayType
+from bisect import bisect_right
 from io import RawIOBase
 from mmap import mmap
 from os import SEEK_CUR, SEEK_END, SEEK_SET
-from typing import Optional, Union
+from typing import List, Optional, Tuple, Union
 
 from .pymicrovmi import MicrovmiExt
 
@@ -19,6 +20,9 @@ class AbstractPhysicalMemoryIO(RawIOBase):
         self._log = logging.getLogger(f"{self.__module__}.{self.__class__.__name__}")
         self._m: MicrovmiExt = m
         self._max_addr: int = self._m.get_max_physical_addr()
+        # RAM ranges (start, end), sorted, everything else is a hole
+        self._memory_map: List[Tuple[int, int]] = self._m.get_memory_map()
+        self._range_starts: List[int] = [start for start, _ in self._memory_map]
         # current seek position in memory (physical address)
         self._cur_pos: int = 0
 
@@ -54,26 +58,53 @@ class AbstractPhysicalMemoryIO(RawIOBase):
     def writable(self) -> bool:
         return False
 
+    def _mapped_ranges(self, addr: int, size: int) -> List[Tuple[int, int]]:
+        """Return the parts of [addr, addr + size) backed by RAM, as (start, end) tuples"""
+        end = addr + size
+        # last range starting before addr, it might contain it
+        index = max(bisect_right(self._range_starts, addr) - 1, 0)
+        mapped = []
+        for range_start, range_end in self._memory_map[index:]:
+            if range_start >= end:
+                break
+            if range_end > addr:
+                mapped.append((max(range_start, addr), min(range_end, end)))
+        return mapped
+
+    def _contiguous_size(self, size: int) -> int:
+        """Return how many bytes can be read from the current position before a hole"""
+        end = self._cur_pos
+        for range_start, range_end in self._mapped_ranges(self._cur_pos, size):
+            if range_start != end:
+                break
+            end = range_end
+        return end - self._cur_pos
+
 
 class PhysicalMemoryIO(AbstractPhysicalMemoryIO):
     """This class provides a Python IO object to work
     with a VM's physical memory as a binary stream
 
     The stream is unbuffered, as we are dealing with live memory.
-    Also, it allows randoms access (seeks) in memory"""
+    Also, it allows randoms access (seeks) in memory
+
+    Reads stop at the first hole of the memory map."""
 
     def read(self, size: int = ...) -> Optional[bytes]:  # type: ignore
         self._log.debug("read: size: %s", size)
         if size < 0:
             # -1: read all bytes until EOF
             raise NotImplementedError
-        data = bytearray(size)
-        bytes_read = self._m.read_physical_into(self._cur_pos, data)
+        data = bytearray(self._contiguous_size(size))
+        bytes_read = self._m.read_physical_into(self._cur_pos, data) if data else 0
         self._log.debug("read return: len: %s, content: %s", len(data), data[:100])
         return bytes(data[:bytes_read])
 
     def readinto(self, buffer: Union[bytearray, memoryview, ArrayType, mmap]) -> Optional[int]:
-        bytes_read = self._m.read_physical_into(self._cur_pos, buffer)
+        view = memoryview(buffer).cast("B")
+        data = bytearray(self._contiguous_size(len(view)))
+        bytes_read = self._m.read_physical_into(self._cur_pos, data) if data else 0
+        view[:bytes_read] = data[:bytes_read]
         return bytes_read
 
 
@@ -95,12 +126,14 @@ class PaddedPhysicalMemoryIO(AbstractPhysicalMemoryIO):
             # -1: read all bytes until EOF
             raise NotImplementedError
         data = bytearray(size)
-        for offset in range(0, size, PAGE_SIZE):
-            read_len = min(PAGE_SIZE, size - offset)
-            pos = self.tell()
-            chunk, _ = self._m.read_physical(pos, read_len)
-            end_offset = offset + read_len
-            data[offset:end_offset] = chunk
-            self.seek(read_len, SEEK_CUR)
+        pos = self.tell()
+        # holes are left zeroed
+        for start, end in self._mapped_ranges(pos, size):
+            for chunk_addr in range(start, end, PAGE_SIZE):
+                read_len = min(PAGE_SIZE, end - chunk_addr)
+                chunk, _ = self._m.read_physical(chunk_addr, read_len)
+                offset = chunk_addr - pos
+                data[offset : offset + read_len] = chunk
+        self.seek(size, SEEK_CUR)
         self._log.debug("read return: len: %s, content: %s", len(data), data[:100])
         return bytes(data)
@@ -1,5 +1,5 @@
 from enum import Enum
-from typing import Optional
+from typing import List, Optional, Tuple
 
 from microvmi.memory import PaddedPhysicalMemoryIO, PhysicalMemoryIO
 
@@ -12,6 +12,11 @@ class DriverType(Enum):
     KVM = 0
     VirtualBox = 1
     Xen = 2
+    File = 3
+    ElfCore = 4
+    LiME = 5
+    CrashDump = 6
+    Mock = 7
 
 
 class Microvmi:
@@ -64,6 +69,11 @@ class Microvmi:
         """Return the maximum physical address"""
         return self._micro.get_max_physical_addr()
 
+    @property
+    def memory_map(self) -> List[Tuple[int, int]]:
+        """Return the physical RAM ranges as (start, end) tuples, end being excluded"""
+        return self._micro.get_memory_map()
+
     def read_physical(self, paddr: int, size: int) -> bytes:
         """Read size bytes of physical memory at paddr
 
@@ -11,7 +11,10 @@ use errors::PyMicrovmiError;
 use microvmi::api as rapi; // rust api
 use microvmi::api::params as rparams; // rust params
 use microvmi::init;
-use params::{CommonInitParamsPy, DriverInitParamsPy, KVMInitParamsPy, MemflowInitParamsPy};
+use params::{
+    CommonInitParamsPy, DriverInitParamsPy, FileInitParamsPy, KVMInitParamsPy, MemflowInitParamsPy,
+    MockInitParamsPy,
+};
 
 /// microvmi Python module declaration
 #[pymodule]
@@ -25,6 +28,8 @@ fn pymicrovmi(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<CommonInitParamsPy>()?;
     m.add_class::<KVMInitParamsPy>()?;
     m.add_class::<MemflowInitParamsPy>()?;
+    m.add_class::<FileInitParamsPy>()?;
+    m.add_class::<MockInitParamsPy>()?;
 
     Ok(())
 }
@@ -41,6 +46,16 @@ impl DriverType {
     const VIRTUALBOX: u32 = 1;
     #[classattr]
     const XEN: u32 = 2;
+    #[classattr]
+    const FILE: u32 = 3;
+    #[classattr]
+    const ELFCORE: u32 = 4;
+    #[classattr]
+    const LIME: u32 = 5;
+    #[classattr]
+    const CRASHDUMP: u32 = 6;
+    #[classattr]
+    const MOCK: u32 = 7;
 }
 
 /// Main class to interact with libmicrovmi
@@ -77,6 +92,11 @@ impl MicrovmiExt {
                 DriverType::KVM => Ok(rapi::DriverType::KVM),
                 DriverType::VIRTUALBOX => Ok(rapi::DriverType::VirtualBox),
                 DriverType::XEN => Ok(rapi::DriverType::Xen),
+                DriverType::FILE => Ok(rapi::DriverType::File),
+                DriverType::ELFCORE => Ok(rapi::DriverType::ElfCore),
+                DriverType::LIME => Ok(rapi::DriverType::LiME),
+                DriverType::CRASHDUMP => Ok(rapi::DriverType::CrashDump),
+                DriverType::MOCK => Ok(rapi::DriverType::Mock),
                 _ => Err(PyValueError::new_err(format!(
                     "Invalid value for DriverType: {}",
                     drv_type
@@ -96,6 +116,14 @@ impl MicrovmiExt {
                     args: k.connector_args,
                 }),
             }),
+            file: v.file.map(|k| rparams::FileInitParams {
+                path: k.path,
+                writable: k.writable,
+            }),
+            mock: v.mock.map(|k| rparams::MockInitParams {
+                memory_size: k.memory_size,
+                vcpu_count: k.vcpu_count,
+            }),
             ..Default::default()
         });
 
@@ -164,4 +192,19 @@ impl MicrovmiExt {
             .map_err(PyMicrovmiError::from)?;
         Ok(max_addr)
     }
+
+    /// get the physical memory map
+    ///
+    /// Returns:
+    ///     List[Tuple[int, int]]: the RAM ranges (start, end), end being excluded
+    fn get_memory_map(&self) -> PyResult<Vec<(u64, u64)>> {
+        let memory_map = self
+            .driver
+            .get_memory_map()
+            .map_err(PyMicrovmiError::from)?;
+        Ok(memory_map
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect())
+    }
 }
@@ -54,6 +54,48 @@ impl MemflowInitParamsPy {
     }
 }
 
+/// equivalent of `FileInitParams` for Python
+#[pyclass]
+#[derive(Default, Debug, Clone)]
+pub struct FileInitParamsPy {
+    #[pyo3(get, set)]
+    pub path: String,
+    #[pyo3(get, set)]
+    pub writable: bool,
+}
+
+#[pymethods]
+impl FileInitParamsPy {
+    #[new]
+    fn new(path: &str) -> Self {
+        Self {
+            path: String::from(path),
+            writable: false,
+        }
+    }
+}
+
+/// equivalent of `MockInitParams` for Python
+#[pyclass]
+#[derive(Default, Debug, Clone)]
+pub struct MockInitParamsPy {
+    #[pyo3(get, set)]
+    pub memory_size: u64,
+    #[pyo3(get, set)]
+    pub vcpu_count: u16,
+}
+
+#[pymethods]
+impl MockInitParamsPy {
+    #[new]
+    fn new(memory_size: u64, vcpu_count: u16) -> Self {
+        Self {
+            memory_size,
+            vcpu_count,
+        }
+    }
+}
+
 /// equivalent of `DriverInitParams` for Python
 ///
 /// # Examples
@@ -81,6 +123,10 @@ pub struct DriverInitParamsPy {
     pub kvm: Option<KVMInitParamsPy>,
     #[pyo3(get, set)]
     pub memflow: Option<MemflowInitParamsPy>,
+    #[pyo3(get, set)]
+    pub file: Option<FileInitParamsPy>,
+    #[pyo3(get, set)]
+    pub mock: Option<MockInitParamsPy>,
 }
 
 #[pymethods]
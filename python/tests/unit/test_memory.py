@@ -0,0 +1,52 @@
+from os import SEEK_SET
+
+from microvmi.memory import PaddedPhysicalMemoryIO, PhysicalMemoryIO
+
+# 16K of memory, with a hole from 0x1800 to 0x3000
+MEMORY = bytes(range(256)) * 64
+MEMORY_MAP = [(0, 0x1000), (0x1000, 0x1800), (0x3000, 0x4000)]
+
+
+def outside_hole(paddr, size):
+    return paddr + size <= 0x1800 or paddr >= 0x3000
+
+
+class FakeMicrovmiExt:
+    def get_max_physical_addr(self):
+        return len(MEMORY)
+
+    def get_memory_map(self):
+        return MEMORY_MAP
+
+    def read_physical(self, paddr, size):
+        assert outside_hole(paddr, size)
+        return MEMORY[paddr : paddr + size], size
+
+    def read_physical_into(self, paddr, buffer):
+        size = len(buffer)
+        assert outside_hole(paddr, size)
+        buffer[:] = MEMORY[paddr : paddr + size]
+        return size
+
+
+def test_read_stops_at_hole():
+    memory = PhysicalMemoryIO(FakeMicrovmiExt())
+    assert MEMORY[:0x1800] == memory.read(0x2000)
+    memory.seek(0x2000, SEEK_SET)
+    assert b"" == memory.read(0x10)
+
+
+def test_readinto_stops_at_hole():
+    memory = PhysicalMemoryIO(FakeMicrovmiExt())
+    memory.seek(0x3000, SEEK_SET)
+    buffer = bytearray(0x2000)
+    assert 0x1000 == memory.readinto(buffer)
+    assert MEMORY[0x3000:0x4000] == buffer[:0x1000]
+
+
+def test_padded_read_zeroes_hole():
+    memory = PaddedPhysicalMemoryIO(FakeMicrovmiExt())
+    memory.seek(0x17F0, SEEK_SET)
+    data = memory.read(0x1820)
+    assert MEMORY[0x17F0:0x1800] + bytes(0x1800) + MEMORY[0x3000:0x3010] == data
+    assert 0x3010 == memory.tell()
@@ -0,0 +1,525 @@
+//! This module implements software breakpoints on top of [`Introspectable`](../trait.Introspectable.html)
+//!
+//! A breakpoint is an int3 instruction written in the guest memory, the original byte being saved.
+//! The page containing the breakpoint is restricted to execute only, so that guest reads and writes
+//! are intercepted and see the original content.
+//!
+//! When a breakpoint is hit, or a hooked page accessed, the original bytes are restored and the vcpu
+//! singlestepped over the instruction, before the breakpoints are re-armed.
+//! Meanwhile, the other vcpus may execute the original code without hitting the breakpoint.
+//!
+//! The Breakpoint and Pagefault intercepts must be enabled by the caller,
+//! the Singlestep intercept is toggled by the manager and must be supported by the driver.
+//!
+//! Hardware breakpoints don't modify the guest memory, they are programmed in the debug registers
+//! of a vcpu with [`set_hardware_breakpoint`](fn.set_hardware_breakpoint.html), which requires the driver
+//! to implement [`Introspectable::write_debug_registers`](../trait.Introspectable.html#method.write_debug_registers).
+//! The debug exceptions they trigger are not reported as events by the drivers: the breakpoints which
+//! have been hit can be found with [`hardware_breakpoints_hit`](fn.hardware_breakpoints_hit.html),
+//! from DR6.
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::api::events::{Event, EventReplyType, EventType, InterceptType};
+use crate::api::memory::{translate, PagingMode};
+use crate::api::registers::DebugRegisters;
+use crate::api::{Access, Introspectable, PAGE_SHIFT};
+use crate::errors::MicrovmiError;
+
+const INT3: u8 = 0xCC;
+// DR0 to DR3
+const DEBUG_ADDRESS_REGISTERS: usize = 4;
+
+#[derive(thiserror::Error, Debug)]
+pub enum BreakpointError {
+    #[error("a breakpoint is already inserted at {0:#X}")]
+    AlreadyInserted(u64),
+    #[error("no breakpoint inserted at {0:#X}")]
+    NotInserted(u64),
+    #[error("invalid debug address register DR{0}")]
+    InvalidDebugRegister(usize),
+    #[error("invalid hardware breakpoint length {0}")]
+    InvalidLength(u8),
+}
+
+impl From<BreakpointError> for MicrovmiError {
+    fn from(error: BreakpointError) -> Self {
+        MicrovmiError::Other {
+            source: Box::new(error),
+        }
+    }
+}
+
+/// Result of [`BreakpointManager::handle_event`](struct.BreakpointManager.html#method.handle_event)
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum BreakpointEvent {
+    /// A managed breakpoint has been hit, the event has been replied
+    Hit { vcpu: u16, gpa: u64 },
+    /// The event was triggered by the breakpoints management, and has been replied
+    Handled,
+    /// The event is not related to the managed breakpoints, and must be replied by the caller
+    NotHandled(Event),
+}
+
+/// Condition of a hardware breakpoint
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HardwareBreakpointKind {
+    /// Instruction execution, the length must be 1
+    Execute,
+    /// Data writes
+    Write,
+    /// Data reads or writes
+    ReadWrite,
+}
+
+/// Program the hardware breakpoint `index` (DR0 to DR3) of a vcpu at a linear address
+///
+/// len is the size of the watched data: 1, 2, 4 or 8 bytes
+pub fn set_hardware_breakpoint<T: Introspectable + ?Sized>(
+    drv: &T,
+    vcpu: u16,
+    index: usize,
+    vaddr: u64,
+    kind: HardwareBreakpointKind,
+    len: u8,
+) -> Result<(), MicrovmiError> {
+    if index >= DEBUG_ADDRESS_REGISTERS {
+        return Err(BreakpointError::InvalidDebugRegister(index).into());
+    }
+    // DR7 R/W and LEN fields
+    let rw: u64 = match kind {
+        HardwareBreakpointKind::Execute => 0b00,
+        HardwareBreakpointKind::Write => 0b01,
+        HardwareBreakpointKind::ReadWrite => 0b11,
+    };
+    let len_bits: u64 = match (kind, len) {
+        (_, 1) => 0b00,
+        (HardwareBreakpointKind::Execute, _) => {
+            return Err(BreakpointError::InvalidLength(len).into())
+        }
+        (_, 2) => 0b01,
+        (_, 4) => 0b11,
+        (_, 8) => 0b10,
+        _ => return Err(BreakpointError::InvalidLength(len).into()),
+    };
+    let mut regs = drv.read_debug_registers(vcpu)?;
+    *debug_address_register(&mut regs, index) = vaddr;
+    regs.dr7 &= !dr7_mask(index);
+    // local enable
+    regs.dr7 |= (1 << (2 * index)) | ((rw | (len_bits << 2)) << (16 + 4 * index));
+    drv.write_debug_registers(vcpu, regs)
+}
+
+/// Disable the hardware breakpoint `index` (DR0 to DR3) of a vcpu
+pub fn clear_hardware_breakpoint<T: Introspectable + ?Sized>(
+    drv: &T,
+    vcpu: u16,
+    index: usize,
+) -> Result<(), MicrovmiError> {
+    if index >= DEBUG_ADDRESS_REGISTERS {
+        return Err(BreakpointError::InvalidDebugRegister(index).into());
+    }
+    let mut regs = drv.read_debug_registers(vcpu)?;
+    *debug_address_register(&mut regs, index) = 0;
+    regs.dr7 &= !dr7_mask(index);
+    drv.write_debug_registers(vcpu, regs)
+}
+
+/// The hardware breakpoints which triggered the last debug exception, according to DR6
+pub fn hardware_breakpoints_hit(regs: &DebugRegisters) -> Vec<usize> {
+    (0..DEBUG_ADDRESS_REGISTERS)
+        .filter(|index| regs.dr6 & (1 << index) != 0)
+        .collect()
+}
+
+fn debug_address_register(regs: &mut DebugRegisters, index: usize) -> &mut u64 {
+    match index {
+        0 => &mut regs.dr0,
+        1 => &mut regs.dr1,
+        2 => &mut regs.dr2,
+        _ => &mut regs.dr3,
+    }
+}
+
+// enable bits, R/W and LEN fields of a breakpoint in DR7
+fn dr7_mask(index: usize) -> u64 {
+    (0b11 << (2 * index)) | (0b1111 << (16 + 4 * index))
+}
+
+#[derive(Debug, Copy, Clone)]
+struct HookedPage {
+    // access before the first breakpoint was inserted
+    access: Access,
+    breakpoints: usize,
+}
+
+/// What a singlestepping vcpu is stepping over
+#[derive(Debug, Copy, Clone)]
+enum Step {
+    Breakpoint(u64),
+    Page(u64),
+}
+
+/// Software breakpoints manager
+#[derive(Debug, Default)]
+pub struct BreakpointManager {
+    // GPA -> original byte
+    breakpoints: HashMap<u64, u8>,
+    // GFN -> hooked page
+    pages: HashMap<u64, HookedPage>,
+    // VCPU -> step in progress
+    steps: HashMap<u16, Step>,
+}
+
+impl BreakpointManager {
+    pub fn new() -> Self {
+        BreakpointManager::default()
+    }
+
+    /// Whether a breakpoint is inserted at gpa
+    pub fn contains(&self, gpa: u64) -> bool {
+        self.breakpoints.contains_key(&gpa)
+    }
+
+    /// Insert a breakpoint at the given guest physical address
+    pub fn insert<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        gpa: u64,
+    ) -> Result<(), MicrovmiError> {
+        if self.contains(gpa) {
+            return Err(BreakpointError::AlreadyInserted(gpa).into());
+        }
+        // a breakpoint which has been hit could never be re-armed
+        if !drv
+            .capabilities()
+            .supports_intercept(InterceptType::Singlestep)
+        {
+            return Err(MicrovmiError::Unsupported("Singlestep intercept"));
+        }
+        let mut orig = [0u8; 1];
+        let mut bytes_read = 0;
+        drv.read_physical(gpa, &mut orig, &mut bytes_read)?;
+        let page = match self.pages.entry(gpa >> PAGE_SHIFT) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let access = drv.get_page_access(gpa)?;
+                drv.set_page_access(gpa, access & Access::X)?;
+                entry.insert(HookedPage {
+                    access,
+                    breakpoints: 0,
+                })
+            }
+        };
+        drv.write_physical(gpa, &[INT3])?;
+        page.breakpoints += 1;
+        self.breakpoints.insert(gpa, orig[0]);
+        Ok(())
+    }
+
+    /// Insert a breakpoint at the given guest virtual address, translated with cr3
+    ///
+    /// Returns the guest physical address of the breakpoint
+    pub fn insert_virtual<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        mode: PagingMode,
+        cr3: u64,
+        vaddr: u64,
+    ) -> Result<u64, MicrovmiError> {
+        let gpa = translate(drv, mode, cr3, vaddr)?;
+        self.insert(drv, gpa)?;
+        Ok(gpa)
+    }
+
+    /// Remove the breakpoint at gpa, restoring the original byte
+    pub fn remove<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        gpa: u64,
+    ) -> Result<(), MicrovmiError> {
+        let orig = *self
+            .breakpoints
+            .get(&gpa)
+            .ok_or(BreakpointError::NotInserted(gpa))?;
+        drv.write_physical(gpa, &[orig])?;
+        self.breakpoints.remove(&gpa);
+        let gfn = gpa >> PAGE_SHIFT;
+        if let Some(page) = self.pages.get_mut(&gfn) {
+            page.breakpoints -= 1;
+            if page.breakpoints == 0 {
+                let access = page.access;
+                self.pages.remove(&gfn);
+                drv.set_page_access(gpa, access)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle an event returned by `listen`
+    ///
+    /// Breakpoint, Singlestep and Pagefault events caused by the managed breakpoints are replied,
+    /// the other ones are given back to the caller.
+    pub fn handle_event<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &mut T,
+        event: Event,
+    ) -> Result<BreakpointEvent, MicrovmiError> {
+        let vcpu = event.vcpu;
+        match event.kind {
+            EventType::Breakpoint { gpa, .. } if self.contains(gpa) => {
+                // execute the original instruction
+                self.start_step(drv, vcpu, Step::Breakpoint(gpa))?;
+                drv.write_physical(gpa, &[self.breakpoints[&gpa]])?;
+                drv.reply_event(event, EventReplyType::Retry)?;
+                Ok(BreakpointEvent::Hit { vcpu, gpa })
+            }
+            EventType::Pagefault { gpa, access, .. }
+                if self.pages.contains_key(&(gpa >> PAGE_SHIFT)) && !access.is_empty() =>
+            {
+                // let the guest access the original page content
+                let gfn = gpa >> PAGE_SHIFT;
+                self.start_step(drv, vcpu, Step::Page(gfn))?;
+                self.write_page_originals(&*drv, gfn)?;
+                drv.set_page_access(gpa, self.pages[&gfn].access)?;
+                drv.reply_event(event, EventReplyType::Retry)?;
+                Ok(BreakpointEvent::Handled)
+            }
+            EventType::Singlestep { .. } if self.steps.contains_key(&vcpu) => {
+                match self.steps.remove(&vcpu) {
+                    // the breakpoint might have been removed meanwhile
+                    Some(Step::Breakpoint(gpa)) if self.contains(gpa) => {
+                        drv.write_physical(gpa, &[INT3])?;
+                    }
+                    Some(Step::Page(gfn)) => {
+                        if let Some(page) = self.pages.get(&gfn) {
+                            let access = page.access;
+                            self.rearm_page(&*drv, gfn)?;
+                            drv.set_page_access(gfn << PAGE_SHIFT, access & Access::X)?;
+                        }
+                    }
+                    _ => (),
+                }
+                drv.toggle_intercept(vcpu, InterceptType::Singlestep, false)?;
+                drv.reply_event(event, EventReplyType::Continue)?;
+                Ok(BreakpointEvent::Handled)
+            }
+            _ => Ok(BreakpointEvent::NotHandled(event)),
+        }
+    }
+
+    /// Singlestep the vcpu once the event is replied
+    ///
+    /// The intercept already enables the stepping on the vcpu, so the event must not be replied
+    /// with `EventReplyType::SingleStep`, which would toggle it back off.
+    /// It is enabled before the original content is restored: on failure the breakpoint is still
+    /// armed and the event can be replied by the caller.
+    fn start_step<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &mut T,
+        vcpu: u16,
+        step: Step,
+    ) -> Result<(), MicrovmiError> {
+        drv.toggle_intercept(vcpu, InterceptType::Singlestep, true)?;
+        self.steps.insert(vcpu, step);
+        Ok(())
+    }
+
+    fn page_breakpoints(&self, gfn: u64) -> Vec<u64> {
+        self.breakpoints
+            .keys()
+            .copied()
+            .filter(|gpa| gpa >> PAGE_SHIFT == gfn)
+            .collect()
+    }
+
+    fn write_page_originals<T: Introspectable + ?Sized>(
+        &self,
+        drv: &T,
+        gfn: u64,
+    ) -> Result<(), MicrovmiError> {
+        for gpa in self.page_breakpoints(gfn) {
+            drv.write_physical(gpa, &[self.breakpoints[&gpa]])?;
+        }
+        Ok(())
+    }
+
+    /// Save the original bytes, which the guest might have modified, and write the breakpoints again
+    fn rearm_page<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        gfn: u64,
+    ) -> Result<(), MicrovmiError> {
+        for gpa in self.page_breakpoints(gfn) {
+            let mut orig = [0u8; 1];
+            let mut bytes_read = 0;
+            drv.read_physical(gpa, &mut orig, &mut bytes_read)?;
+            self.breakpoints.insert(gpa, orig[0]);
+            drv.write_physical(gpa, &[INT3])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::fake::FakeDriver;
+    use crate::api::Intercepts;
+
+    use super::*;
+
+    fn event(kind: EventType) -> Event {
+        Event {
+            vcpu: 0,
+            kind,
+            regs: None,
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let drv = FakeDriver::new(0x4000, 0x90);
+        let mut manager = BreakpointManager::new();
+        manager.insert(&drv, 0x1010).unwrap();
+        manager.insert(&drv, 0x1020).unwrap();
+        assert!(manager.insert(&drv, 0x1010).is_err());
+        assert_eq!(INT3, drv.byte(0x1010));
+        assert_eq!(Access::X, drv.get_page_access(0x1000).unwrap());
+
+        manager.remove(&drv, 0x1010).unwrap();
+        assert_eq!(0x90, drv.byte(0x1010));
+        // still one breakpoint in the page
+        assert_eq!(Access::X, drv.get_page_access(0x1000).unwrap());
+        manager.remove(&drv, 0x1020).unwrap();
+        assert_eq!(Access::RWX, drv.get_page_access(0x1000).unwrap());
+        assert!(manager.remove(&drv, 0x1020).is_err());
+    }
+
+    #[test]
+    fn test_insert_requires_singlestep() {
+        let mut drv = FakeDriver::new(0x4000, 0x90);
+        drv.intercepts.remove(Intercepts::SINGLESTEP);
+        let mut manager = BreakpointManager::new();
+        assert!(matches!(
+            manager.insert(&drv, 0x1010),
+            Err(MicrovmiError::Unsupported(_))
+        ));
+        assert_eq!(0x90, drv.byte(0x1010));
+        assert_eq!(Access::RWX, drv.get_page_access(0x1000).unwrap());
+    }
+
+    #[test]
+    fn test_breakpoint_hit_is_rearmed_after_singlestep() {
+        let mut drv = FakeDriver::new(0x4000, 0x90);
+        let mut manager = BreakpointManager::new();
+        manager.insert(&drv, 0x1010).unwrap();
+
+        let result = manager
+            .handle_event(
+                &mut drv,
+                event(EventType::Breakpoint {
+                    gpa: 0x1010,
+                    insn_len: 1,
+                }),
+            )
+            .unwrap();
+        assert!(matches!(
+            result,
+            BreakpointEvent::Hit {
+                vcpu: 0,
+                gpa: 0x1010
+            }
+        ));
+        assert_eq!(0x90, drv.byte(0x1010));
+        assert!(drv.singlestep);
+        assert!(matches!(drv.replies[0], EventReplyType::Retry));
+
+        let result = manager
+            .handle_event(&mut drv, event(EventType::Singlestep { gpa: 0x1011 }))
+            .unwrap();
+        assert!(matches!(result, BreakpointEvent::Handled));
+        assert_eq!(INT3, drv.byte(0x1010));
+        assert!(!drv.singlestep);
+        assert!(matches!(drv.replies[1], EventReplyType::Continue));
+
+        // unknown breakpoint, belonging to the guest
+        let result = manager
+            .handle_event(
+                &mut drv,
+                event(EventType::Breakpoint {
+                    gpa: 0x2000,
+                    insn_len: 1,
+                }),
+            )
+            .unwrap();
+        assert!(matches!(result, BreakpointEvent::NotHandled(_)));
+    }
+
+    #[test]
+    fn test_guest_write_is_hidden() {
+        let mut drv = FakeDriver::new(0x4000, 0x90);
+        let mut manager = BreakpointManager::new();
+        manager.insert(&drv, 0x1010).unwrap();
+
+        let result = manager
+            .handle_event(
+                &mut drv,
+                event(EventType::Pagefault {
+                    gva: 0x1010,
+                    gpa: 0x1010,
+                    access: Access::W,
+                }),
+            )
+            .unwrap();
+        assert!(matches!(result, BreakpointEvent::Handled));
+        // the guest sees and modifies the original content
+        assert_eq!(0x90, drv.byte(0x1010));
+        assert_eq!(Access::RWX, drv.get_page_access(0x1000).unwrap());
+        drv.write_physical(0x1010, &[0x55]).unwrap();
+
+        manager
+            .handle_event(&mut drv, event(EventType::Singlestep { gpa: 0x1100 }))
+            .unwrap();
+        assert_eq!(INT3, drv.byte(0x1010));
+        assert_eq!(Access::X, drv.get_page_access(0x1000).unwrap());
+        // the new content is restored on removal
+        manager.remove(&drv, 0x1010).unwrap();
+        assert_eq!(0x55, drv.byte(0x1010));
+    }
+
+    #[test]
+    fn test_hardware_breakpoints() {
+        let drv = FakeDriver::new(0x4000, 0x90);
+        set_hardware_breakpoint(
+            &drv,
+            0,
+            0,
+            0xFFFF_8000_0000_1000,
+            HardwareBreakpointKind::Execute,
+            1,
+        )
+        .unwrap();
+        set_hardware_breakpoint(&drv, 0, 2, 0x7FFE_0000, HardwareBreakpointKind::Write, 4).unwrap();
+        let dr7 = drv.debug_regs.borrow().dr7;
+        assert_eq!(0xFFFF_8000_0000_1000, drv.debug_regs.borrow().dr0);
+        assert_eq!(0x7FFE_0000, drv.debug_regs.borrow().dr2);
+        // L0, L2, RW2 = 01, LEN2 = 11
+        assert_eq!((1 << 0) | (1 << 4) | (0b1101 << 24), dr7);
+        assert!(set_hardware_breakpoint(&drv, 0, 4, 0, HardwareBreakpointKind::Write, 1).is_err());
+        assert!(
+            set_hardware_breakpoint(&drv, 0, 1, 0, HardwareBreakpointKind::Execute, 4).is_err()
+        );
+        assert!(set_hardware_breakpoint(&drv, 0, 1, 0, HardwareBreakpointKind::Write, 3).is_err());
+
+        clear_hardware_breakpoint(&drv, 0, 0).unwrap();
+        assert_eq!((1 << 4) | (0b1101 << 24), drv.debug_regs.borrow().dr7);
+        assert_eq!(0, drv.debug_regs.borrow().dr0);
+
+        let regs = DebugRegisters {
+            dr6: 0xFFFF_0FF4,
+            ..Default::default()
+        };
+        assert_eq!(vec![2], hardware_breakpoints_hit(&regs));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::api::registers::Registers;
 use crate::api::Access;
 
 /// Various types of intercepts handled by libmicrovmi
@@ -10,7 +11,13 @@ pub enum InterceptType {
     Msr(u32),
     /// Intercept when guest requests an access to a page for which the requested type of access is not granted. For example , guest tries to write on a read only page.
     Breakpoint,
+    /// Intercept the accesses to the pages restricted with set_page_access.
+    /// On Xen, these events are always reported and toggling this intercept has no effect.
     Pagefault,
+    /// Intercept each instruction executed by the vcpu
+    ///
+    /// Only available on Xen, KVMi does not expose singlestepping
+    Singlestep,
 }
 
 /// Various types of events along with their relevant attributes being handled by this driver
@@ -48,6 +55,11 @@ pub enum EventType {
         /// Acsess responsible for thr pagefault
         access: Access,
     },
+    ///singlestep interception, after the execution of an instruction
+    Singlestep {
+        /// Physical memory address of the next instruction
+        gpa: u64,
+    },
 }
 
 ///Types of x86 control registers are listed here
@@ -64,16 +76,31 @@ pub enum CrType {
 
 ///This provides an abstraction of event which the hypervisor reports and using which we introspect the guest
 #[repr(C)]
+#[derive(Debug)]
 pub struct Event {
     ///vcpu on which the event is detected
     pub vcpu: u16,
     /// Type of event detected
     pub kind: EventType,
+    /// Registers of the vcpu, captured when the event was intercepted, if provided by the driver
+    pub regs: Option<Registers>,
 }
 
 ///Reply provided to the hypervisor after detecting an event
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum EventReplyType {
+    /// Let the guest continue, with the default behavior for the event
     Continue,
+    /// Set the vcpu registers before continuing
+    SetRegisters(Registers),
+    /// Toggle singlestepping on the vcpu
+    SingleStep,
+    /// Emulate the instruction which triggered the event (ignoring the page access restrictions)
+    Emulate,
+    /// Skip the instruction which triggered the event (only for events reporting an instruction length)
+    SkipInstruction,
+    /// Re-execute the instruction which triggered the event, usually after the page access or memory has been modified
+    Retry,
 }
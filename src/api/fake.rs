@@ -0,0 +1,135 @@
+//! This module implements an in-memory driver, shared by the unit tests of the api helpers
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::api::events::{Event, EventReplyType, InterceptType};
+use crate::api::registers::{DebugRegisters, Registers, X86Registers};
+use crate::api::{
+    Access, Capabilities, DriverType, Intercepts, Introspectable, Operations, PAGE_SHIFT,
+};
+use crate::errors::MicrovmiError;
+
+/// Driver backed by a buffer of guest physical memory, which records the intercepts and replies
+#[derive(Default)]
+pub struct FakeDriver {
+    pub mem: RefCell<Vec<u8>>,
+    // gfn -> access, RWX if missing
+    pub page_access: RefCell<HashMap<u64, Access>>,
+    pub regs: RefCell<X86Registers>,
+    pub debug_regs: RefCell<DebugRegisters>,
+    pub intercepts: Intercepts,
+    pub singlestep: bool,
+    pub replies: Vec<EventReplyType>,
+}
+
+impl FakeDriver {
+    /// Guest physical memory of the given size, filled with byte
+    pub fn new(size: usize, byte: u8) -> Self {
+        FakeDriver {
+            mem: RefCell::new(vec![byte; size]),
+            intercepts: Intercepts::BREAKPOINT | Intercepts::PAGEFAULT | Intercepts::SINGLESTEP,
+            ..Default::default()
+        }
+    }
+
+    pub fn byte(&self, paddr: u64) -> u8 {
+        self.mem.borrow()[paddr as usize]
+    }
+
+    /// Write the first size bytes of a little endian paging structure entry
+    pub fn set_entry(&self, paddr: u64, entry: u64, size: usize) {
+        let start = paddr as usize;
+        self.mem.borrow_mut()[start..start + size].copy_from_slice(&entry.to_le_bytes()[..size]);
+    }
+
+    pub fn access(&self, gfn: u64) -> Access {
+        self.get_page_access(gfn << PAGE_SHIFT).unwrap()
+    }
+}
+
+impl Introspectable for FakeDriver {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::EVENTS,
+            intercepts: self.intercepts,
+        }
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        let start: usize = paddr.try_into()?;
+        buf.copy_from_slice(&self.mem.borrow()[start..start + buf.len()]);
+        *bytes_read = buf.len() as u64;
+        Ok(())
+    }
+
+    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
+        let start: usize = paddr.try_into()?;
+        self.mem.borrow_mut()[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn read_registers(&self, _vcpu: u16) -> Result<Registers, MicrovmiError> {
+        Ok(Registers::X86(self.regs.borrow().clone()))
+    }
+
+    fn write_registers(&self, _vcpu: u16, reg: Registers) -> Result<(), MicrovmiError> {
+        let Registers::X86(regs) = reg;
+        *self.regs.borrow_mut() = regs;
+        Ok(())
+    }
+
+    fn read_debug_registers(&self, _vcpu: u16) -> Result<DebugRegisters, MicrovmiError> {
+        Ok(*self.debug_regs.borrow())
+    }
+
+    fn write_debug_registers(&self, _vcpu: u16, regs: DebugRegisters) -> Result<(), MicrovmiError> {
+        *self.debug_regs.borrow_mut() = regs;
+        Ok(())
+    }
+
+    fn get_page_access(&self, paddr: u64) -> Result<Access, MicrovmiError> {
+        Ok(*self
+            .page_access
+            .borrow()
+            .get(&(paddr >> PAGE_SHIFT))
+            .unwrap_or(&Access::RWX))
+    }
+
+    fn set_page_access(&self, paddr: u64, access: Access) -> Result<(), MicrovmiError> {
+        self.page_access
+            .borrow_mut()
+            .insert(paddr >> PAGE_SHIFT, access);
+        Ok(())
+    }
+
+    fn toggle_intercept(
+        &mut self,
+        _vcpu: u16,
+        intercept_type: InterceptType,
+        enabled: bool,
+    ) -> Result<(), MicrovmiError> {
+        if let InterceptType::Singlestep = intercept_type {
+            self.singlestep = enabled;
+        }
+        Ok(())
+    }
+
+    fn reply_event(
+        &mut self,
+        _event: Event,
+        reply_type: EventReplyType,
+    ) -> Result<(), MicrovmiError> {
+        self.replies.push(reply_type);
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::File
+    }
+}
@@ -0,0 +1,387 @@
+//! This module implements guest virtual memory access on top of [`Introspectable::read_physical`](../trait.Introspectable.html#method.read_physical)
+//!
+//! Virtual addresses are translated by walking the x86 page tables found at a given CR3.
+//! The following paging modes are supported, including large pages:
+//! - 32 bits (4K and 4M pages)
+//! - PAE (4K and 2M pages)
+//! - IA-32e 4-level paging (4K, 2M and 1G pages)
+//! - IA-32e 5-level paging (4K, 2M and 1G pages)
+use crate::api::registers::{Registers, X86Registers};
+use crate::api::{Introspectable, PAGE_SHIFT, PAGE_SIZE};
+use crate::errors::MicrovmiError;
+
+const PAGE_PRESENT: u64 = 1 << 0;
+const PAGE_SIZE_BIT: u64 = 1 << 7;
+// physical address bits 51:12 of a 64 bits paging structure entry
+const PAGE_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const CR0_PG: u64 = 1 << 31;
+const CR4_PSE: u64 = 1 << 4;
+const CR4_PAE: u64 = 1 << 5;
+const CR4_LA57: u64 = 1 << 12;
+const EFER_LMA: u64 = 1 << 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MemoryError {
+    #[error("virtual address {vaddr:#X} is not mapped (entry not present at level {level})")]
+    PageNotPresent { vaddr: u64, level: u8 },
+    #[error("paging is disabled")]
+    PagingDisabled,
+}
+
+impl From<MemoryError> for MicrovmiError {
+    fn from(error: MemoryError) -> Self {
+        match error {
+            MemoryError::PageNotPresent { vaddr, .. } => MicrovmiError::InvalidAddress {
+                address: vaddr,
+                source: Box::new(error),
+            },
+            MemoryError::PagingDisabled => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// x86 paging modes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PagingMode {
+    /// 32 bits paging, 2 levels
+    ///
+    /// 4M pages are only used when page size extensions are enabled (CR4.PSE).
+    Legacy { pse: bool },
+    /// Physical Address Extension, 3 levels
+    Pae,
+    /// IA-32e 4-level paging
+    Level4,
+    /// IA-32e 5-level paging
+    Level5,
+}
+
+impl PagingMode {
+    /// Determine the paging mode from the control registers of a VCPU
+    ///
+    /// Returns `None` if paging is disabled.
+    pub fn from_registers(regs: &X86Registers) -> Option<Self> {
+        if regs.cr0 & CR0_PG == 0 {
+            return None;
+        }
+        // depending on the driver, EFER is reported in one field or the other
+        let efer = regs.efer | regs.msr_efer;
+        Some(if efer & EFER_LMA != 0 {
+            if regs.cr4 & CR4_LA57 != 0 {
+                PagingMode::Level5
+            } else {
+                PagingMode::Level4
+            }
+        } else if regs.cr4 & CR4_PAE != 0 {
+            PagingMode::Pae
+        } else {
+            PagingMode::Legacy {
+                pse: regs.cr4 & CR4_PSE != 0,
+            }
+        })
+    }
+}
+
+/// Determine the paging mode of the guest from the control registers of its first VCPU
+pub fn paging_mode<T: Introspectable + ?Sized>(drv: &T) -> Result<PagingMode, MicrovmiError> {
+    let Registers::X86(regs) = drv.read_registers(0)?;
+    Ok(PagingMode::from_registers(&regs).ok_or(MemoryError::PagingDisabled)?)
+}
+
+fn read_entry<T: Introspectable + ?Sized>(
+    drv: &T,
+    paddr: u64,
+    size: usize,
+) -> Result<u64, MicrovmiError> {
+    let mut buf = [0u8; 8];
+    let mut bytes_read = 0;
+    drv.read_physical(paddr, &mut buf[..size], &mut bytes_read)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Translate a guest virtual address into a guest physical address
+///
+/// # Arguments
+/// * 'drv' - the driver used to read the page tables
+/// * 'mode' - the paging mode of the guest
+/// * 'cr3' - the page tables root
+/// * 'vaddr' - the virtual address to translate
+///
+pub fn translate<T: Introspectable + ?Sized>(
+    drv: &T,
+    mode: PagingMode,
+    cr3: u64,
+    vaddr: u64,
+) -> Result<u64, MicrovmiError> {
+    if let PagingMode::Legacy { pse } = mode {
+        // 4 bytes entries, 10 bits indexes
+        let vaddr = vaddr & 0xFFFF_FFFF;
+        let pde_addr = (cr3 & 0xFFFF_F000) + ((vaddr >> 22) << 2);
+        let pde = read_entry(drv, pde_addr, 4)?;
+        if pde & PAGE_PRESENT == 0 {
+            return Err(MemoryError::PageNotPresent { vaddr, level: 2 }.into());
+        }
+        if pse && pde & PAGE_SIZE_BIT != 0 {
+            // 4M page, PSE-36 stores physical address bits 39:32 in bits 20:13
+            let high = ((pde >> 13) & 0xFF) << 32;
+            return Ok(high | (pde & 0xFFC0_0000) | (vaddr & 0x3F_FFFF));
+        }
+        let pte_addr = (pde & 0xFFFF_F000) + (((vaddr >> PAGE_SHIFT) & 0x3FF) << 2);
+        let pte = read_entry(drv, pte_addr, 4)?;
+        if pte & PAGE_PRESENT == 0 {
+            return Err(MemoryError::PageNotPresent { vaddr, level: 1 }.into());
+        }
+        return Ok((pte & 0xFFFF_F000) | (vaddr & 0xFFF));
+    }
+
+    // 8 bytes entries, 9 bits indexes
+    // shift of the virtual address bits indexing each level, from the root
+    let (mut table, vaddr, shifts): (u64, u64, &[u32]) = match mode {
+        PagingMode::Pae => (cr3 & 0xFFFF_FFE0, vaddr & 0xFFFF_FFFF, &[30, 21, 12]),
+        PagingMode::Level4 => (cr3 & PAGE_ADDR_MASK, vaddr, &[39, 30, 21, 12]),
+        _ => (cr3 & PAGE_ADDR_MASK, vaddr, &[48, 39, 30, 21, 12]),
+    };
+    for (i, &shift) in shifts.iter().enumerate() {
+        let level = (shifts.len() - i) as u8;
+        let entry_addr = table + (((vaddr >> shift) & 0x1FF) << 3);
+        let entry = read_entry(drv, entry_addr, 8)?;
+        if entry & PAGE_PRESENT == 0 {
+            return Err(MemoryError::PageNotPresent { vaddr, level }.into());
+        }
+        if shift == PAGE_SHIFT {
+            return Ok((entry & PAGE_ADDR_MASK) | (vaddr & u64::from(PAGE_SIZE - 1)));
+        }
+        // 1G and 2M pages, PAE PDPTEs have no PS bit
+        let large_page_level = shift == 21 || (shift == 30 && mode != PagingMode::Pae);
+        if large_page_level && entry & PAGE_SIZE_BIT != 0 {
+            let offset_mask = (1u64 << shift) - 1;
+            return Ok((entry & PAGE_ADDR_MASK & !offset_mask) | (vaddr & offset_mask));
+        }
+        table = entry & PAGE_ADDR_MASK;
+    }
+    unreachable!("the last level always maps a 4K page")
+}
+
+/// Read the guest virtual memory, starting from vaddr, into buf
+///
+/// The read is split on page boundaries, each page being translated independently.
+/// bytes_read is updated with the amount of bytes read before an error occured.
+pub fn read_virtual<T: Introspectable + ?Sized>(
+    drv: &T,
+    mode: PagingMode,
+    cr3: u64,
+    vaddr: u64,
+    buf: &mut [u8],
+    bytes_read: &mut u64,
+) -> Result<(), MicrovmiError> {
+    *bytes_read = 0;
+    let mut buf_offset: usize = 0;
+    while buf_offset < buf.len() {
+        let cur_vaddr = vaddr.wrapping_add(buf_offset as u64);
+        let page_offset = (cur_vaddr & u64::from(PAGE_SIZE - 1)) as usize;
+        let read_len = std::cmp::min(PAGE_SIZE as usize - page_offset, buf.len() - buf_offset);
+        let paddr = translate(drv, mode, cr3, cur_vaddr)?;
+        let mut chunk_read = 0;
+        let result = drv.read_physical(
+            paddr,
+            &mut buf[buf_offset..buf_offset + read_len],
+            &mut chunk_read,
+        );
+        *bytes_read += chunk_read;
+        result?;
+        buf_offset += read_len;
+    }
+    Ok(())
+}
+
+/// Modify the guest virtual memory, starting at vaddr, from buf
+///
+/// The write is split on page boundaries, each page being translated independently.
+pub fn write_virtual<T: Introspectable + ?Sized>(
+    drv: &T,
+    mode: PagingMode,
+    cr3: u64,
+    vaddr: u64,
+    buf: &[u8],
+) -> Result<(), MicrovmiError> {
+    let mut buf_offset: usize = 0;
+    while buf_offset < buf.len() {
+        let cur_vaddr = vaddr.wrapping_add(buf_offset as u64);
+        let page_offset = (cur_vaddr & u64::from(PAGE_SIZE - 1)) as usize;
+        let write_len = std::cmp::min(PAGE_SIZE as usize - page_offset, buf.len() - buf_offset);
+        let paddr = translate(drv, mode, cr3, cur_vaddr)?;
+        drv.write_physical(paddr, &buf[buf_offset..buf_offset + write_len])?;
+        buf_offset += write_len;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::fake::FakeDriver;
+
+    use super::*;
+
+    #[test]
+    fn test_translate_level4() {
+        let drv = FakeDriver::new(0x10000, 0);
+        let vaddr: u64 = 0xFFFF_8000_0020_1123;
+        // PML4 at 0x1000, PDPT at 0x2000, PD at 0x3000, PT at 0x4000
+        drv.set_entry(0x1000 + ((vaddr >> 39) & 0x1FF) * 8, 0x2003, 8);
+        drv.set_entry(0x2000 + ((vaddr >> 30) & 0x1FF) * 8, 0x3003, 8);
+        drv.set_entry(0x3000 + ((vaddr >> 21) & 0x1FF) * 8, 0x4003, 8);
+        // NX bit set on the PTE
+        drv.set_entry(
+            0x4000 + ((vaddr >> 12) & 0x1FF) * 8,
+            0x8000_0000_0000_5003,
+            8,
+        );
+        assert_eq!(
+            0x5123,
+            translate(&drv, PagingMode::Level4, 0x1000, vaddr).unwrap()
+        );
+        // not present
+        assert!(matches!(
+            translate(&drv, PagingMode::Level4, 0x1000, vaddr + 0x1000),
+            Err(MicrovmiError::InvalidAddress { address, .. }) if address == vaddr + 0x1000
+        ));
+    }
+
+    #[test]
+    fn test_translate_level4_large_pages() {
+        let drv = FakeDriver::new(0x10000, 0);
+        // 2M page
+        drv.set_entry(0x1000, 0x2003, 8);
+        drv.set_entry(0x2000, 0x3003, 8);
+        drv.set_entry(0x3000, 0x4060_0083, 8);
+        assert_eq!(
+            0x4061_2345,
+            translate(&drv, PagingMode::Level4, 0x1000, 0x1_2345).unwrap()
+        );
+        // 1G page
+        drv.set_entry(0x2008, 0x8000_0083, 8);
+        assert_eq!(
+            0x8123_4567,
+            translate(&drv, PagingMode::Level4, 0x1000, 0x4123_4567).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_translate_legacy_and_pae() {
+        let drv = FakeDriver::new(0x10000, 0);
+        // legacy: PD at 0x1000, PT at 0x2000, 4M page for the second PDE
+        drv.set_entry(0x1000, 0x2003, 4);
+        drv.set_entry(0x2004, 0x7003, 4);
+        drv.set_entry(0x1004, 0x0080_0083, 4);
+        assert_eq!(
+            0x7ABC,
+            translate(&drv, PagingMode::Legacy { pse: true }, 0x1000, 0x1ABC).unwrap()
+        );
+        assert_eq!(
+            0x0081_2345,
+            translate(&drv, PagingMode::Legacy { pse: true }, 0x1000, 0x0041_2345).unwrap()
+        );
+        // without PSE, the PS bit is ignored and the PDE points to a page table
+        drv.set_entry(0x1008, 0x6083, 4);
+        drv.set_entry(0x6000 + 0x12 * 4, 0x9003, 4);
+        assert_eq!(
+            0x9345,
+            translate(&drv, PagingMode::Legacy { pse: false }, 0x1000, 0x0081_2345).unwrap()
+        );
+        // PAE: PDPT at 0x3020, PD at 0x4000, PT at 0x5000
+        drv.set_entry(0x3020, 0x4001, 8);
+        drv.set_entry(0x4000, 0x5003, 8);
+        drv.set_entry(0x5008, 0x9003, 8);
+        assert_eq!(
+            0x9010,
+            translate(&drv, PagingMode::Pae, 0x3020, 0x1010).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_read_write_virtual_across_pages() {
+        let drv = FakeDriver::new(0x10000, 0);
+        // 5-level: vaddr 0x0 -> 0x8000, vaddr 0x1000 -> 0x6000
+        drv.set_entry(0x1000, 0x2003, 8);
+        drv.set_entry(0x2000, 0x3003, 8);
+        drv.set_entry(0x3000, 0x4003, 8);
+        drv.set_entry(0x4000, 0x5003, 8);
+        drv.set_entry(0x5000, 0x8003, 8);
+        drv.set_entry(0x5008, 0x6003, 8);
+        write_virtual(&drv, PagingMode::Level5, 0x1000, 0xFFE, &[1, 2, 3, 4]).unwrap();
+        assert_eq!([1, 2], drv.mem.borrow()[0x8FFE..0x9000]);
+        assert_eq!([3, 4], drv.mem.borrow()[0x6000..0x6002]);
+
+        let mut buf = [0u8; 4];
+        let mut bytes_read = 0;
+        read_virtual(
+            &drv,
+            PagingMode::Level5,
+            0x1000,
+            0xFFE,
+            &mut buf,
+            &mut bytes_read,
+        )
+        .unwrap();
+        assert_eq!([1, 2, 3, 4], buf);
+        assert_eq!(4, bytes_read);
+
+        // the third page is not mapped
+        assert!(read_virtual(
+            &drv,
+            PagingMode::Level5,
+            0x1000,
+            0x1FFE,
+            &mut buf,
+            &mut bytes_read
+        )
+        .is_err());
+        assert_eq!(2, bytes_read);
+    }
+
+    #[test]
+    fn test_read_write_virtual_use_guest_paging_mode() {
+        let drv = FakeDriver::new(0x10000, 0);
+        // PAE: PDPT at 0x3020, PD at 0x4000, PT at 0x5000
+        drv.set_entry(0x3020, 0x4001, 8);
+        drv.set_entry(0x4000, 0x5003, 8);
+        drv.set_entry(0x5008, 0x9003, 8);
+        // paging disabled
+        let mut buf = [0u8; 2];
+        let mut bytes_read = 0;
+        assert!(drv
+            .read_virtual(0x3020, 0x1010, &mut buf, &mut bytes_read)
+            .is_err());
+
+        drv.regs.borrow_mut().cr0 = CR0_PG;
+        drv.regs.borrow_mut().cr4 = CR4_PAE;
+        drv.write_virtual(0x3020, 0x1010, &[0xAB, 0xCD]).unwrap();
+        assert_eq!([0xAB, 0xCD], drv.mem.borrow()[0x9010..0x9012]);
+        drv.read_virtual(0x3020, 0x1010, &mut buf, &mut bytes_read)
+            .unwrap();
+        assert_eq!([0xAB, 0xCD], buf);
+    }
+
+    #[test]
+    fn test_paging_mode_from_registers() {
+        let mut regs = X86Registers::default();
+        assert_eq!(None, PagingMode::from_registers(&regs));
+        regs.cr0 = CR0_PG;
+        assert_eq!(
+            Some(PagingMode::Legacy { pse: false }),
+            PagingMode::from_registers(&regs)
+        );
+        regs.cr4 = CR4_PSE;
+        assert_eq!(
+            Some(PagingMode::Legacy { pse: true }),
+            PagingMode::from_registers(&regs)
+        );
+        regs.cr4 = CR4_PAE;
+        assert_eq!(Some(PagingMode::Pae), PagingMode::from_registers(&regs));
+        regs.msr_efer = EFER_LMA;
+        assert_eq!(Some(PagingMode::Level4), PagingMode::from_registers(&regs));
+        regs.cr4 |= CR4_LA57;
+        assert_eq!(Some(PagingMode::Level5), PagingMode::from_registers(&regs));
+    }
+}
@@ -1,12 +1,18 @@
 use enum_iterator::IntoEnumIterator;
-use std::error::Error;
 
 use events::{Event, EventReplyType, InterceptType};
-use registers::Registers;
+use registers::{DebugRegisters, ExtendedRegisters, Registers};
 
+use crate::errors::MicrovmiError;
+
+pub mod breakpoints;
 pub mod events;
+#[cfg(test)]
+mod fake;
+pub mod memory;
 pub mod params;
 pub mod registers;
+pub mod watch;
 
 bitflags! {
     pub struct Access: u32 {
@@ -21,6 +27,91 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Introspectable operations implemented by a driver
+    #[derive(Default)]
+    pub struct Operations: u32 {
+        const VCPU_COUNT=1 << 0;
+        const READ_PHYSICAL=1 << 1;
+        const WRITE_PHYSICAL=1 << 2;
+        const MAX_PHYSICAL_ADDR=1 << 3;
+        const READ_REGISTERS=1 << 4;
+        const WRITE_REGISTERS=1 << 5;
+        const GET_PAGE_ACCESS=1 << 6;
+        const SET_PAGE_ACCESS=1 << 7;
+        const PAUSE=1 << 8;
+        const RESUME=1 << 9;
+        /// toggle_intercept, listen and reply_event
+        const EVENTS=1 << 10;
+        const READ_DEBUG_REGISTERS=1 << 11;
+        const WRITE_DEBUG_REGISTERS=1 << 12;
+        const READ_MSR=1 << 13;
+        const WRITE_MSR=1 << 14;
+        const READ_EXTENDED_REGISTERS=1 << 15;
+    }
+}
+
+bitflags! {
+    /// Intercept types which can be enabled with toggle_intercept
+    #[derive(Default)]
+    pub struct Intercepts: u32 {
+        const CR=1 << 0;
+        const MSR=1 << 1;
+        const BREAKPOINT=1 << 2;
+        const PAGEFAULT=1 << 3;
+        const SINGLESTEP=1 << 4;
+    }
+}
+
+impl From<InterceptType> for Intercepts {
+    fn from(intercept_type: InterceptType) -> Self {
+        match intercept_type {
+            InterceptType::Cr(_) => Intercepts::CR,
+            InterceptType::Msr(_) => Intercepts::MSR,
+            InterceptType::Breakpoint => Intercepts::BREAKPOINT,
+            InterceptType::Pagefault => Intercepts::PAGEFAULT,
+            InterceptType::Singlestep => Intercepts::SINGLESTEP,
+        }
+    }
+}
+
+/// The operations and intercepts supported by a driver
+///
+/// Calling an operation which is not supported returns a `MicrovmiError::Unsupported` error.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    pub operations: Operations,
+    pub intercepts: Intercepts,
+}
+
+impl Capabilities {
+    /// Whether the given intercept type can be enabled
+    pub fn supports_intercept(&self, intercept_type: InterceptType) -> bool {
+        self.operations.contains(Operations::EVENTS)
+            && self.intercepts.contains(Intercepts::from(intercept_type))
+    }
+}
+
+/// A range of guest physical memory backed by RAM
+///
+/// Physical addresses which are not covered by any range of the memory map are holes
+/// (MMIO, reserved, or simply absent from a dump).
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MemoryRange {
+    /// start physical address
+    pub start: u64,
+    /// end physical address (exclusive)
+    pub end: u64,
+}
+
+impl MemoryRange {
+    /// Whether the given physical address is part of the range
+    pub fn contains(&self, paddr: u64) -> bool {
+        self.start <= paddr && paddr < self.end
+    }
+}
+
 ///Represents the available hypervisor VMI drivers supported by libmicrovmi
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq, IntoEnumIterator)]
@@ -29,6 +120,11 @@ pub enum DriverType {
     Memflow,
     VirtualBox,
     Xen,
+    File,
+    ElfCore,
+    LiME,
+    CrashDump,
+    Mock,
 }
 
 // impl TryInto<DriverInitParam> for DriverInitParamFFI {
@@ -49,10 +145,18 @@ pub const PAGE_SHIFT: u32 = 12;
 pub const PAGE_SIZE: u32 = 4096;
 
 pub trait Introspectable {
+    /// Query the operations and intercepts supported by the driver
+    ///
+    /// The default implementation reports that nothing is supported.
+    ///
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
     /// Retrieve the number of VCPUs.
     ///
-    fn get_vcpu_count(&self) -> Result<u16, Box<dyn Error>> {
-        unimplemented!();
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("get_vcpu_count"))
     }
 
     /// read the physical memory, starting from paddr, into buf
@@ -68,8 +172,8 @@ pub trait Introspectable {
         _paddr: u64,
         _buf: &mut [u8],
         _bytes_read: &mut u64,
-    ) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    ) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("read_physical"))
     }
 
     /// Modify contents of physical memory, starting at paddr, from buf
@@ -79,16 +183,72 @@ pub trait Introspectable {
     /// * 'paddr' - the physical address to write into
     /// * 'buf' - the data to be written into memory
     ///
-    fn write_physical(&self, _paddr: u64, _buf: &[u8]) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn write_physical(&self, _paddr: u64, _buf: &[u8]) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("write_physical"))
     }
 
     /// Get the maximum physical address
     ///
     /// Returns maximum physical address in 64 bit unsigned integer format.
     ///
-    fn get_max_physical_addr(&self) -> Result<u64, Box<dyn Error>> {
-        unimplemented!();
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("get_max_physical_addr"))
+    }
+
+    /// read the virtual memory, starting from vaddr, into buf
+    ///
+    /// The address is translated with the page tables rooted at cr3, the paging mode being
+    /// derived from the control registers of the first VCPU.
+    /// When the registers are not available, use [`memory::read_virtual`](memory/fn.read_virtual.html)
+    /// with an explicit paging mode.
+    ///
+    /// # Arguments
+    ///
+    /// * 'cr3' - the page tables root
+    /// * 'vaddr' - the virtual address to read from
+    /// * 'buf' - the data read from memory
+    /// * 'bytes_read' - the number of bytes read
+    ///
+    fn read_virtual(
+        &self,
+        cr3: u64,
+        vaddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        let mode = memory::paging_mode(self)?;
+        memory::read_virtual(self, mode, cr3, vaddr, buf, bytes_read)
+    }
+
+    /// Modify contents of virtual memory, starting at vaddr, from buf
+    ///
+    /// The address is translated with the page tables rooted at cr3, the paging mode being
+    /// derived from the control registers of the first VCPU.
+    /// When the registers are not available, use [`memory::write_virtual`](memory/fn.write_virtual.html)
+    /// with an explicit paging mode.
+    ///
+    /// # Arguments
+    ///
+    /// * 'cr3' - the page tables root
+    /// * 'vaddr' - the virtual address to write into
+    /// * 'buf' - the data to be written into memory
+    ///
+    fn write_virtual(&self, cr3: u64, vaddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
+        let mode = memory::paging_mode(self)?;
+        memory::write_virtual(self, mode, cr3, vaddr, buf)
+    }
+
+    /// Get the physical memory map
+    ///
+    /// Returns the RAM ranges, sorted by physical address.
+    /// The default implementation reports a single range, up to the maximum physical address.
+    /// It is used by the Xen and KVM drivers, whose APIs don't expose the guest memory layout.
+    ///
+    fn get_memory_map(&self) -> Result<Vec<MemoryRange>, MicrovmiError> {
+        Ok(vec![MemoryRange {
+            start: 0,
+            end: self.get_max_physical_addr()?,
+        }])
     }
 
     /// Read register values
@@ -96,8 +256,8 @@ pub trait Introspectable {
     /// # Arguments
     /// * 'vcpu' - vcpu id for which the value of registers are to be dumped as the argument
     ///
-    fn read_registers(&self, _vcpu: u16) -> Result<Registers, Box<dyn Error>> {
-        unimplemented!();
+    fn read_registers(&self, _vcpu: u16) -> Result<Registers, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("read_registers"))
     }
 
     ///get page access
@@ -105,8 +265,8 @@ pub trait Introspectable {
     /// # Arguments
     /// * 'paddr' - physical address of the page whose access we want to know.
     ///
-    fn get_page_access(&self, _paddr: u64) -> Result<Access, Box<dyn Error>> {
-        unimplemented!();
+    fn get_page_access(&self, _paddr: u64) -> Result<Access, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("get_page_access"))
     }
 
     ///set page access
@@ -115,8 +275,8 @@ pub trait Introspectable {
     /// * 'paddr' - physical address of the page whose access we want to set
     /// * 'access' - access flags to be set on the given page
     ///
-    fn set_page_access(&self, _paddr: u64, _access: Access) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn set_page_access(&self, _paddr: u64, _access: Access) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("set_page_access"))
     }
 
     /// Write register values
@@ -125,20 +285,75 @@ pub trait Introspectable {
     /// * 'vcpu' - vcpu id for which the value of registers are to be set
     /// * 'reg' - Registers enum having values to be set
     ///
-    fn write_registers(&self, _vcpu: u16, _reg: Registers) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn write_registers(&self, _vcpu: u16, _reg: Registers) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("write_registers"))
+    }
+
+    /// Read the x87 FPU, SSE and AVX registers
+    ///
+    /// The AVX and AVX-512 parts are only available when the driver can access the XSAVE area.
+    ///
+    /// # Arguments
+    /// * 'vcpu' - vcpu id for which the registers are read
+    ///
+    fn read_extended_registers(&self, _vcpu: u16) -> Result<ExtendedRegisters, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("read_extended_registers"))
+    }
+
+    /// Read the debug registers (DR0-DR3, DR6 and DR7)
+    ///
+    /// # Arguments
+    /// * 'vcpu' - vcpu id for which the registers are read
+    ///
+    fn read_debug_registers(&self, _vcpu: u16) -> Result<DebugRegisters, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("read_debug_registers"))
+    }
+
+    /// Write the debug registers (DR0-DR3, DR6 and DR7)
+    ///
+    /// # Arguments
+    /// * 'vcpu' - vcpu id for which the registers are written
+    /// * 'regs' - the new debug registers values
+    ///
+    fn write_debug_registers(
+        &self,
+        _vcpu: u16,
+        _regs: DebugRegisters,
+    ) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("write_debug_registers"))
+    }
+
+    /// Read a model specific register
+    ///
+    /// # Arguments
+    /// * 'vcpu' - vcpu id for which the MSR is read
+    /// * 'index' - MSR index (ex: 0xC0000102 for IA32_KERNEL_GS_BASE)
+    ///
+    fn read_msr(&self, _vcpu: u16, _index: u32) -> Result<u64, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("read_msr"))
+    }
+
+    /// Write a model specific register
+    ///
+    /// # Arguments
+    /// * 'vcpu' - vcpu id for which the MSR is written
+    /// * 'index' - MSR index
+    /// * 'value' - the new MSR value
+    ///
+    fn write_msr(&self, _vcpu: u16, _index: u32, _value: u64) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("write_msr"))
     }
 
     /// Used to pause the VM
     ///
-    fn pause(&mut self) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("pause"))
     }
 
     /// Used to resume the VM
     ///
-    fn resume(&mut self) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("resume"))
     }
 
     /// Used to enable/disable an event interception
@@ -153,8 +368,8 @@ pub trait Introspectable {
         _vcpu: u16,
         _intercept_type: InterceptType,
         _enabled: bool,
-    ) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    ) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("toggle_intercept"))
     }
 
     /// Listen and return the next event, or None
@@ -162,8 +377,8 @@ pub trait Introspectable {
     /// # Arguments
     /// * 'timeout' - Time for which it will wait for a new event
     ///
-    fn listen(&mut self, _timeout: u32) -> Result<Option<Event>, Box<dyn Error>> {
-        unimplemented!();
+    fn listen(&mut self, _timeout: u32) -> Result<Option<Event>, MicrovmiError> {
+        Err(MicrovmiError::Unsupported("listen"))
     }
 
     /// Send reply corresponding to the current event being popped
@@ -176,10 +391,53 @@ pub trait Introspectable {
         &mut self,
         _event: Event,
         _reply_type: EventReplyType,
-    ) -> Result<(), Box<dyn Error>> {
-        unimplemented!();
+    ) -> Result<(), MicrovmiError> {
+        Err(MicrovmiError::Unsupported("reply_event"))
     }
 
     /// Return the concrete DriverType
     fn get_driver_type(&self) -> DriverType;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Empty;
+
+    impl Introspectable for Empty {
+        fn get_driver_type(&self) -> DriverType {
+            DriverType::File
+        }
+    }
+
+    #[test]
+    fn test_default_methods_are_unsupported() {
+        let mut drv = Empty;
+        assert_eq!(Capabilities::default(), drv.capabilities());
+        assert!(matches!(
+            drv.pause(),
+            Err(MicrovmiError::Unsupported("pause"))
+        ));
+        assert!(drv.read_registers(0).is_err());
+        assert!(matches!(
+            drv.read_msr(0, 0xC000_0080),
+            Err(MicrovmiError::Unsupported("read_msr"))
+        ));
+    }
+
+    #[test]
+    fn test_supports_intercept() {
+        let capabilities = Capabilities {
+            operations: Operations::EVENTS,
+            intercepts: Intercepts::CR | Intercepts::BREAKPOINT,
+        };
+        assert!(capabilities.supports_intercept(InterceptType::Breakpoint));
+        assert!(!capabilities.supports_intercept(InterceptType::Msr(0)));
+        let no_events = Capabilities {
+            operations: Operations::READ_PHYSICAL,
+            ..capabilities
+        };
+        assert!(!no_events.supports_intercept(InterceptType::Breakpoint));
+    }
+}
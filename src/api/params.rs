@@ -57,6 +57,27 @@ pub struct MemflowInitParams {
 #[derive(Debug, Clone, PartialEq)]
 pub enum VBoxInitParams {}
 
+/// Memory dump file initialization parameters
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileInitParams {
+    /// path to the memory dump file
+    pub path: String,
+    /// allow write_physical
+    ///
+    /// the file is mapped copy-on-write: modifications are only visible to this driver instance
+    /// and never written back to disk
+    pub writable: bool,
+}
+
+/// Mock driver initialization parameters
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MockInitParams {
+    /// size of the zeroed guest physical memory
+    pub memory_size: u64,
+    /// number of VCPUs, each starting with zeroed registers
+    pub vcpu_count: u16,
+}
+
 /// Common initialization parameters
 ///
 /// These parameters are shared by two or more drivers, and are stored in this struct
@@ -74,7 +95,7 @@ pub struct CommonInitParams {
 /// ```no_run
 /// // Xen
 /// // common.vm_name: mandatory
-/// use microvmi::api::params::{DriverInitParams, CommonInitParams, KVMInitParams, MemflowInitParams};
+/// use microvmi::api::params::{DriverInitParams, CommonInitParams, KVMInitParams, MemflowInitParams, FileInitParams, MockInitParams};
 /// let init_params = DriverInitParams {
 ///     common: Some(CommonInitParams { vm_name: String::from("windows10")}),
 ///     ..Default::default()
@@ -101,6 +122,20 @@ pub struct CommonInitParams {
 ///         ..Default::default()}),
 ///     ..Default::default()
 /// };
+/// // File
+/// // file.path: mandatory
+/// // file.writable: optional
+/// let init_params = DriverInitParams {
+///     file: Some(FileInitParams { path: String::from("/tmp/windows10.raw"),
+///         ..Default::default()}),
+///     ..Default::default()
+/// };
+/// // Mock
+/// // mock: mandatory
+/// let init_params = DriverInitParams {
+///     mock: Some(MockInitParams { memory_size: 0x10000, vcpu_count: 1 }),
+///     ..Default::default()
+/// };
 /// ```
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct DriverInitParams {
@@ -109,4 +144,6 @@ pub struct DriverInitParams {
     pub kvm: Option<KVMInitParams>,
     pub memflow: Option<MemflowInitParams>,
     pub virtualbox: Option<VBoxInitParams>,
+    pub file: Option<FileInitParams>,
+    pub mock: Option<MockInitParams>,
 }
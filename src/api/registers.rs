@@ -1,6 +1,6 @@
 ///an x86 segment register
 #[repr(C)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct SegmentReg {
     ///Stores the base address of a code segment
     pub base: u64,
@@ -13,7 +13,7 @@ pub struct SegmentReg {
 /// x86 System Table Registers
 /// (GDTR, IDTR)
 #[repr(C)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct SystemTableReg {
     /// 32/64 bits linear base address
     pub base: u64,
@@ -23,7 +23,7 @@ pub struct SystemTableReg {
 
 ///Represents all x86 registers on a specific VCPU
 #[repr(C)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct X86Registers {
     /// 8 byte general purpose register.
     pub rax: u64,
@@ -106,7 +106,202 @@ pub struct X86Registers {
 }
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Registers {
     X86(X86Registers),
 }
+
+///x86 debug registers of a VCPU, which program the hardware breakpoints
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DebugRegisters {
+    ///Debug address register, linear address of the hardware breakpoint 0
+    pub dr0: u64,
+    ///Debug address register, linear address of the hardware breakpoint 1
+    pub dr1: u64,
+    ///Debug address register, linear address of the hardware breakpoint 2
+    pub dr2: u64,
+    ///Debug address register, linear address of the hardware breakpoint 3
+    pub dr3: u64,
+    ///Debug status register, reports the conditions which triggered the last debug exception
+    pub dr6: u64,
+    ///Debug control register, enables the hardware breakpoints and selects their conditions
+    pub dr7: u64,
+}
+
+// offsets in the XSAVE area (standard format)
+const FXSAVE_ST: usize = 32;
+const FXSAVE_XMM: usize = 160;
+const FXSAVE_SIZE: usize = 512;
+const XSAVE_XSTATE_BV: usize = 512;
+const XSAVE_YMM_HI128: usize = 576;
+const XSAVE_ZMM_HI256: usize = 1152;
+const XSAVE_HI16_ZMM: usize = 1664;
+const XSAVE_SIZE: usize = 2688;
+// XSAVE state components
+const XSTATE_YMM: u64 = 1 << 2;
+const XSTATE_ZMM_HI256: u64 = 1 << 6;
+const XSTATE_HI16_ZMM: u64 = 1 << 7;
+
+///x87 FPU, SSE and AVX registers of a VCPU
+#[derive(Debug, Clone)]
+pub struct ExtendedRegisters {
+    ///x87 FPU control word
+    pub fcw: u16,
+    ///x87 FPU status word
+    pub fsw: u16,
+    ///x87 FPU abridged tag word
+    pub ftw: u8,
+    ///x87 FPU last instruction opcode
+    pub fop: u16,
+    ///x87 FPU last instruction pointer
+    pub fip: u64,
+    ///x87 FPU last data pointer
+    pub fdp: u64,
+    ///SSE control and status register
+    pub mxcsr: u32,
+    ///Supported MXCSR bits
+    pub mxcsr_mask: u32,
+    ///ST0-ST7 / MM0-MM7, 80 bits values padded to 16 bytes
+    pub st: [[u8; 16]; 8],
+    ///ZMM0-ZMM31, little endian. XMMn and YMMn are the low 16 and 32 bytes of ZMMn
+    pub zmm: [[u8; 64]; 32],
+    ///XCR0, the state components enabled by the guest
+    pub xcr0: u64,
+}
+
+impl Default for ExtendedRegisters {
+    fn default() -> Self {
+        ExtendedRegisters {
+            fcw: 0,
+            fsw: 0,
+            ftw: 0,
+            fop: 0,
+            fip: 0,
+            fdp: 0,
+            mxcsr: 0,
+            mxcsr_mask: 0,
+            st: [[0; 16]; 8],
+            zmm: [[0; 64]; 32],
+            xcr0: 0,
+        }
+    }
+}
+
+impl ExtendedRegisters {
+    /// Parse the 512 bytes legacy region saved by FXSAVE (x87, MXCSR, XMM0-XMM15)
+    pub fn from_fxsave(area: &[u8]) -> Self {
+        let mut regs = ExtendedRegisters::default();
+        if area.len() < FXSAVE_SIZE {
+            return regs;
+        }
+        regs.fcw = u16::from_le_bytes([area[0], area[1]]);
+        regs.fsw = u16::from_le_bytes([area[2], area[3]]);
+        regs.ftw = area[4];
+        regs.fop = u16::from_le_bytes([area[6], area[7]]);
+        regs.fip = read_u64(area, 8);
+        regs.fdp = read_u64(area, 16);
+        regs.mxcsr = read_u32(area, 24);
+        regs.mxcsr_mask = read_u32(area, 28);
+        for (i, st) in regs.st.iter_mut().enumerate() {
+            let start = FXSAVE_ST + 16 * i;
+            st.copy_from_slice(&area[start..start + 16]);
+        }
+        for (i, zmm) in regs.zmm.iter_mut().take(16).enumerate() {
+            let start = FXSAVE_XMM + 16 * i;
+            zmm[..16].copy_from_slice(&area[start..start + 16]);
+        }
+        regs
+    }
+
+    /// Parse an area saved by XSAVE, in the standard format
+    ///
+    /// The AVX and AVX-512 components are only read if they are present in the area (XSTATE_BV).
+    pub fn from_xsave(area: &[u8], xcr0: u64) -> Self {
+        let mut regs = ExtendedRegisters::from_fxsave(area);
+        regs.xcr0 = xcr0;
+        if area.len() < XSAVE_XSTATE_BV + 8 {
+            return regs;
+        }
+        let xstate_bv = read_u64(area, XSAVE_XSTATE_BV);
+        if xstate_bv & XSTATE_YMM != 0 && area.len() >= XSAVE_YMM_HI128 + 16 * 16 {
+            for (i, zmm) in regs.zmm.iter_mut().take(16).enumerate() {
+                let start = XSAVE_YMM_HI128 + 16 * i;
+                zmm[16..32].copy_from_slice(&area[start..start + 16]);
+            }
+        }
+        if xstate_bv & XSTATE_ZMM_HI256 != 0 && area.len() >= XSAVE_ZMM_HI256 + 16 * 32 {
+            for (i, zmm) in regs.zmm.iter_mut().take(16).enumerate() {
+                let start = XSAVE_ZMM_HI256 + 32 * i;
+                zmm[32..].copy_from_slice(&area[start..start + 32]);
+            }
+        }
+        if xstate_bv & XSTATE_HI16_ZMM != 0 && area.len() >= XSAVE_SIZE {
+            for (i, zmm) in regs.zmm.iter_mut().skip(16).enumerate() {
+                let start = XSAVE_HI16_ZMM + 64 * i;
+                zmm.copy_from_slice(&area[start..start + 64]);
+            }
+        }
+        regs
+    }
+
+    /// XMMn register
+    pub fn xmm(&self, index: usize) -> [u8; 16] {
+        let mut xmm = [0; 16];
+        xmm.copy_from_slice(&self.zmm[index][..16]);
+        xmm
+    }
+
+    /// YMMn register
+    pub fn ymm(&self, index: usize) -> [u8; 32] {
+        let mut ymm = [0; 32];
+        ymm.copy_from_slice(&self.zmm[index][..32]);
+        ymm
+    }
+}
+
+fn read_u32(area: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&area[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(area: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&area[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_registers_from_xsave() {
+        let mut area = vec![0u8; XSAVE_SIZE];
+        area[0..2].copy_from_slice(&0x037Fu16.to_le_bytes());
+        area[24..28].copy_from_slice(&0x1F80u32.to_le_bytes());
+        // ST7
+        area[FXSAVE_ST + 16 * 7] = 0x11;
+        // XMM1, upper half of YMM1
+        area[FXSAVE_XMM + 16..FXSAVE_XMM + 32].copy_from_slice(&[0xAA; 16]);
+        area[XSAVE_YMM_HI128 + 16..XSAVE_YMM_HI128 + 32].copy_from_slice(&[0xBB; 16]);
+        // ZMM31
+        area[XSAVE_SIZE - 64..].copy_from_slice(&[0xCC; 64]);
+
+        // AVX state not saved
+        let regs = ExtendedRegisters::from_xsave(&area, 0x7);
+        assert_eq!(0x037F, regs.fcw);
+        assert_eq!(0x1F80, regs.mxcsr);
+        assert_eq!(0x11, regs.st[7][0]);
+        assert_eq!([0xAA; 16], regs.xmm(1));
+        assert_eq!([0; 16], regs.ymm(1)[16..]);
+        assert_eq!(0x7, regs.xcr0);
+
+        area[XSAVE_XSTATE_BV..XSAVE_XSTATE_BV + 8].copy_from_slice(&0xE7u64.to_le_bytes());
+        let regs = ExtendedRegisters::from_xsave(&area, 0xE7);
+        assert_eq!([0xBB; 16], regs.ymm(1)[16..]);
+        assert_eq!([0xCC; 64], regs.zmm[31]);
+        assert_eq!([0; 64], regs.zmm[30]);
+    }
+}
@@ -0,0 +1,266 @@
+//! This module implements memory watchpoints on top of [`Introspectable::set_page_access`](../trait.Introspectable.html#method.set_page_access)
+//!
+//! A watch covers a range of guest physical memory, for a combination of read, write and execute accesses.
+//! Several watches can share a page: the page access is restricted with the union of their accesses.
+//!
+//! Pagefault events on a watched page are dispatched to the watches whose range and access match,
+//! and the faulting instruction is emulated, so that the guest is not affected by the restrictions.
+//!
+//! The Pagefault intercept must be enabled by the caller.
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use crate::api::events::{Event, EventReplyType, EventType};
+use crate::api::{Access, Introspectable, PAGE_SHIFT};
+use crate::errors::MicrovmiError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatchError {
+    #[error("invalid watch range at {gpa:#X} (length {len:#X})")]
+    InvalidRange { gpa: u64, len: u64 },
+    #[error("unknown watch {0:?}")]
+    UnknownWatch(WatchId),
+}
+
+impl From<WatchError> for MicrovmiError {
+    fn from(error: WatchError) -> Self {
+        MicrovmiError::Other {
+            source: Box::new(error),
+        }
+    }
+}
+
+/// Identifier of a watch, returned by [`WatchManager::add`](struct.WatchManager.html#method.add)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WatchId(usize);
+
+/// Result of [`WatchManager::handle_event`](struct.WatchManager.html#method.handle_event)
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum WatchEvent {
+    /// The access matched at least one watch, the event has been replied
+    Hit {
+        vcpu: u16,
+        gva: u64,
+        gpa: u64,
+        access: Access,
+        watches: Vec<WatchId>,
+    },
+    /// The access hit a watched page, but none of its watches, the event has been replied
+    Handled,
+    /// The event is not related to the watches, and must be replied by the caller
+    NotHandled(Event),
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Watch {
+    gpa: u64,
+    len: u64,
+    access: Access,
+}
+
+impl Watch {
+    fn contains(&self, gpa: u64) -> bool {
+        gpa >= self.gpa && gpa - self.gpa < self.len
+    }
+
+    fn gfns(&self) -> std::ops::RangeInclusive<u64> {
+        (self.gpa >> PAGE_SHIFT)..=((self.gpa + self.len - 1) >> PAGE_SHIFT)
+    }
+}
+
+/// Memory watchpoints manager
+#[derive(Debug, Default)]
+pub struct WatchManager {
+    watches: HashMap<WatchId, Watch>,
+    next_id: usize,
+    // GFN -> access before the first watch was added
+    pages: HashMap<u64, Access>,
+}
+
+impl WatchManager {
+    pub fn new() -> Self {
+        WatchManager::default()
+    }
+
+    /// Watch the accesses to the guest physical range [gpa, gpa + len)
+    pub fn add<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        gpa: u64,
+        len: u64,
+        access: Access,
+    ) -> Result<WatchId, MicrovmiError> {
+        if len == 0 || access.is_empty() || gpa.checked_add(len).is_none() {
+            return Err(WatchError::InvalidRange { gpa, len }.into());
+        }
+        let watch = Watch { gpa, len, access };
+        for gfn in watch.gfns() {
+            if let Entry::Vacant(entry) = self.pages.entry(gfn) {
+                entry.insert(drv.get_page_access(gfn << PAGE_SHIFT)?);
+            }
+        }
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watches.insert(id, watch);
+        for gfn in watch.gfns() {
+            self.update_page(drv, gfn)?;
+        }
+        Ok(id)
+    }
+
+    /// Remove a watch, restoring the page access if it was the last one on the page
+    pub fn remove<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        id: WatchId,
+    ) -> Result<(), MicrovmiError> {
+        let watch = self
+            .watches
+            .remove(&id)
+            .ok_or(WatchError::UnknownWatch(id))?;
+        for gfn in watch.gfns() {
+            self.update_page(drv, gfn)?;
+        }
+        Ok(())
+    }
+
+    /// Handle an event returned by `listen`
+    ///
+    /// Pagefault events on the watched pages are replied, the other ones are given back to the caller.
+    pub fn handle_event<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &mut T,
+        event: Event,
+    ) -> Result<WatchEvent, MicrovmiError> {
+        let vcpu = event.vcpu;
+        match event.kind {
+            EventType::Pagefault { gva, gpa, access }
+                if self.pages.contains_key(&(gpa >> PAGE_SHIFT)) =>
+            {
+                let mut watches: Vec<WatchId> = self
+                    .watches
+                    .iter()
+                    .filter(|(_, watch)| watch.contains(gpa) && watch.access.intersects(access))
+                    .map(|(id, _)| *id)
+                    .collect();
+                watches.sort();
+                // let the access complete, the restrictions stay in place
+                drv.reply_event(event, EventReplyType::Emulate)?;
+                if watches.is_empty() {
+                    return Ok(WatchEvent::Handled);
+                }
+                Ok(WatchEvent::Hit {
+                    vcpu,
+                    gva,
+                    gpa,
+                    access,
+                    watches,
+                })
+            }
+            _ => Ok(WatchEvent::NotHandled(event)),
+        }
+    }
+
+    /// Restrict the page access with the union of its watches accesses
+    fn update_page<T: Introspectable + ?Sized>(
+        &mut self,
+        drv: &T,
+        gfn: u64,
+    ) -> Result<(), MicrovmiError> {
+        let orig = match self.pages.get(&gfn) {
+            Some(access) => *access,
+            None => return Ok(()),
+        };
+        let watched = self
+            .watches
+            .values()
+            .filter(|watch| watch.gfns().contains(&gfn))
+            .fold(Access::NIL, |acc, watch| acc | watch.access);
+        if watched.is_empty() {
+            self.pages.remove(&gfn);
+        }
+        drv.set_page_access(gfn << PAGE_SHIFT, orig & !watched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::fake::FakeDriver;
+
+    use super::*;
+
+    fn pagefault(gpa: u64, access: Access) -> Event {
+        Event {
+            vcpu: 1,
+            kind: EventType::Pagefault {
+                gva: gpa,
+                gpa,
+                access,
+            },
+            regs: None,
+        }
+    }
+
+    #[test]
+    fn test_page_access_is_the_union_of_watches() {
+        let drv = FakeDriver::default();
+        let mut manager = WatchManager::new();
+        // spans pages 1 and 2
+        let write = manager.add(&drv, 0x1FF0, 0x20, Access::W).unwrap();
+        let exec = manager.add(&drv, 0x2100, 0x10, Access::X).unwrap();
+        assert_eq!(Access::RX, drv.access(1));
+        assert_eq!(Access::R, drv.access(2));
+        assert!(manager.add(&drv, 0x3000, 0, Access::R).is_err());
+
+        manager.remove(&drv, write).unwrap();
+        assert_eq!(Access::RWX, drv.access(1));
+        assert_eq!(Access::RW, drv.access(2));
+        manager.remove(&drv, exec).unwrap();
+        assert_eq!(Access::RWX, drv.access(2));
+        assert!(manager.remove(&drv, exec).is_err());
+    }
+
+    #[test]
+    fn test_dispatch_to_matching_watches() {
+        let mut drv = FakeDriver::default();
+        let mut manager = WatchManager::new();
+        let first = manager.add(&drv, 0x1000, 0x10, Access::W).unwrap();
+        let second = manager.add(&drv, 0x1008, 0x10, Access::RW).unwrap();
+
+        match manager
+            .handle_event(&mut drv, pagefault(0x100A, Access::W))
+            .unwrap()
+        {
+            WatchEvent::Hit { vcpu, watches, .. } => {
+                assert_eq!(1, vcpu);
+                assert_eq!(vec![first, second], watches);
+            }
+            _ => panic!("expected a hit"),
+        }
+        // read in the first range, which only watches writes
+        assert!(matches!(
+            manager
+                .handle_event(&mut drv, pagefault(0x1004, Access::R))
+                .unwrap(),
+            WatchEvent::Handled
+        ));
+        // watched page, but outside of the ranges
+        assert!(matches!(
+            manager
+                .handle_event(&mut drv, pagefault(0x1800, Access::W))
+                .unwrap(),
+            WatchEvent::Handled
+        ));
+        assert_eq!(3, drv.replies.len());
+        assert!(matches!(drv.replies[0], EventReplyType::Emulate));
+        // unwatched page
+        assert!(matches!(
+            manager
+                .handle_event(&mut drv, pagefault(0x5000, Access::W))
+                .unwrap(),
+            WatchEvent::NotHandled(_)
+        ));
+        assert_eq!(3, drv.replies.len());
+    }
+}
@@ -1,5 +1,5 @@
 use bitflags::_core::ptr::null_mut;
-use cty::{c_char, size_t, uint16_t, uint64_t, uint8_t};
+use cty::{c_char, size_t, uint16_t, uint32_t, uint64_t, uint8_t};
 
 use std::ffi::{c_void, CString};
 use std::slice;
@@ -183,6 +183,64 @@ pub unsafe extern "C" fn microvmi_read_registers(
     }
 }
 
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn microvmi_read_msr(
+    context: *mut c_void,
+    vcpu: uint16_t,
+    index: uint32_t,
+    value: *mut uint64_t,
+) -> bool {
+    if context.is_null() || value.is_null() {
+        return false;
+    }
+    let driver = get_driver_mut_ptr(context);
+    match (*driver).read_msr(vcpu, index) {
+        Ok(msr_value) => {
+            value.write(msr_value);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn microvmi_write_msr(
+    context: *mut c_void,
+    vcpu: uint16_t,
+    index: uint32_t,
+    value: uint64_t,
+) -> bool {
+    if context.is_null() {
+        return false;
+    }
+    let driver = get_driver_mut_ptr(context);
+    (*driver).write_msr(vcpu, index, value).is_ok()
+}
+
+/// retrieve the operations and intercepts supported by the driver, as bitflags
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub unsafe extern "C" fn microvmi_get_capabilities(
+    context: *mut c_void,
+    operations: *mut uint32_t,
+    intercepts: *mut uint32_t,
+) -> bool {
+    if context.is_null() {
+        return false;
+    }
+    let driver = get_driver_mut_ptr(context);
+    let capabilities = (*driver).capabilities();
+    if !operations.is_null() {
+        operations.write(capabilities.operations.bits());
+    }
+    if !intercepts.is_null() {
+        intercepts.write(capabilities.intercepts.bits());
+    }
+    true
+}
+
 /// return the concrete DriverType for the given Microvmi driver
 #[allow(clippy::missing_safety_doc)]
 #[no_mangle]
@@ -1,5 +1,6 @@
 use crate::api::params::{
-    CommonInitParams, DriverInitParams, KVMInitParams, MemflowConnectorParams, MemflowInitParams,
+    CommonInitParams, DriverInitParams, FileInitParams, KVMInitParams, MemflowConnectorParams,
+    MemflowInitParams,
 };
 use std::convert::TryFrom;
 use std::ffi::{CStr, IntoStringError};
@@ -39,6 +40,14 @@ pub struct MemflowInitParamsFFI {
     pub connector_args: MemflowConnectorParamsFFI,
 }
 
+/// equivalent of `FileInitParams` with C compatibility
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct FileInitParamsFFI {
+    pub path: *mut c_char,
+    pub writable: bool,
+}
+
 /// equivalent of `DriverInitParam` with C compatibility
 #[repr(C)]
 #[derive(Debug, Clone)]
@@ -46,6 +55,7 @@ pub struct DriverInitParamsFFI {
     pub common: CommonInitParamsFFI,
     pub kvm: KVMInitParamsFFI,
     pub memflow: MemflowInitParamsFFI,
+    pub file: FileInitParamsFFI,
 }
 
 // convert from FFI type to Rust type
@@ -104,10 +114,22 @@ impl TryFrom<DriverInitParamsFFI> for DriverInitParams {
                 connector_args: Some(MemflowConnectorParams::Default { args }),
             })
         };
+        // build file params
+        let file = if value.file.path.is_null() {
+            None
+        } else {
+            Some(FileInitParams {
+                path: unsafe { CStr::from_ptr(value.file.path) }
+                    .to_owned()
+                    .into_string()?,
+                writable: value.file.writable,
+            })
+        };
         Ok(DriverInitParams {
             common,
             kvm,
             memflow,
+            file,
             ..Default::default()
         })
     }
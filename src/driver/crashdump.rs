@@ -0,0 +1,573 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+
+use memmap2::{Mmap, MmapOptions};
+
+use crate::api::params::DriverInitParams;
+use crate::api::registers::{Registers, SegmentReg, X86Registers};
+use crate::api::{
+    Capabilities, DriverType, Introspectable, MemoryRange, Operations, PAGE_SHIFT, PAGE_SIZE,
+};
+use crate::errors::MicrovmiError;
+
+const DUMP_SIGNATURE: &[u8; 4] = b"PAGE";
+const DUMP_VALID_DUMP64: &[u8; 4] = b"DU64";
+const DUMP_HEADER64_SIZE: usize = 0x2000;
+
+// DUMP_HEADER64 field offsets
+const DIRECTORY_TABLE_BASE_OFFSET: usize = 0x10;
+const NUMBER_PROCESSORS_OFFSET: usize = 0x34;
+const PHYSICAL_MEMORY_BLOCK_OFFSET: usize = 0x88;
+const PHYSICAL_MEMORY_BLOCK_SIZE: usize = 0x2C0;
+const CONTEXT_RECORD_OFFSET: usize = 0x348;
+const DUMP_TYPE_OFFSET: usize = 0xF98;
+
+// DUMP_TYPE values
+const DUMP_TYPE_FULL: u32 = 0x1;
+const DUMP_TYPE_SUMMARY: u32 = 0x2;
+const DUMP_TYPE_BITMAP: u32 = 0x5;
+const DUMP_TYPE_BITMAP_KERNEL: u32 = 0x6;
+const DUMP_TYPE_KERNEL_MEMORY: u32 = 0x8;
+const DUMP_TYPE_KERNEL_AND_USER_MEMORY: u32 = 0x9;
+const DUMP_TYPE_COMPLETE_MEMORY: u32 = 0xA;
+
+// a 64 bits Windows kernel always runs with 4-level paging enabled
+const CR0_PE: u64 = 1 << 0;
+const CR0_PG: u64 = 1 << 31;
+const CR4_PAE: u64 = 1 << 5;
+const EFER_LME: u64 = 1 << 8;
+const EFER_LMA: u64 = 1 << 10;
+
+// BMP_HEADER64 field offsets, relative to the end of DUMP_HEADER64
+const BMP_VALID_DUMP: &[u8; 4] = b"DUMP";
+const BMP_FIRST_PAGE_OFFSET: usize = 0x20;
+const BMP_PAGES_OFFSET: usize = 0x30;
+const BMP_BITMAP_OFFSET: usize = 0x38;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CrashDumpDriverError {
+    #[error("crash dump driver requires a path parameter")]
+    MissingPath,
+    #[error("not a 64 bits Windows crash dump (PAGEDU64)")]
+    InvalidHeader,
+    #[error("unsupported crash dump type {0:#X}")]
+    UnsupportedDumpType(u32),
+    #[error("crash dump file is truncated at offset {0:#X}")]
+    Truncated(usize),
+    #[error("physical address {0:#X} is not present in the crash dump")]
+    UnmappedAddress(u64),
+    #[error("the crash dump only contains the context of vcpu 0, got vcpu {0}")]
+    MissingContext(u16),
+}
+
+impl From<CrashDumpDriverError> for MicrovmiError {
+    fn from(error: CrashDumpDriverError) -> Self {
+        match error {
+            CrashDumpDriverError::UnmappedAddress(paddr) => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// A run of physical pages stored contiguously in the dump
+#[derive(Debug)]
+struct Run {
+    paddr: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// Windows kernel crash dump driver
+///
+/// Supports 64 bits full, kernel summary and bitmap crash dumps.
+pub struct CrashDump {
+    mmap: Mmap,
+    // sorted by physical address
+    runs: Vec<Run>,
+    vcpu_count: u16,
+    context: X86Registers,
+}
+
+fn get_bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], CrashDumpDriverError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(CrashDumpDriverError::Truncated(offset))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, CrashDumpDriverError> {
+    get_bytes(data, offset, 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, CrashDumpDriverError> {
+    get_bytes(data, offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, CrashDumpDriverError> {
+    get_bytes(data, offset, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// convert a page number or count read from the dump into bytes
+fn page_to_bytes(page: u64) -> Result<u64, CrashDumpDriverError> {
+    page.checked_mul(PAGE_SIZE as u64)
+        .ok_or(CrashDumpDriverError::InvalidHeader)
+}
+
+/// parse the PHYSICAL_MEMORY_DESCRIPTOR64 runs of a full dump
+/// pages are stored run after run, right after the header
+fn parse_full_dump_runs(data: &[u8]) -> Result<Vec<Run>, CrashDumpDriverError> {
+    let number_of_runs = read_u32(data, PHYSICAL_MEMORY_BLOCK_OFFSET)? as usize;
+    // NumberOfRuns, NumberOfPages, then (BasePage, PageCount) pairs
+    if number_of_runs > (PHYSICAL_MEMORY_BLOCK_SIZE - 16) / 16 {
+        return Err(CrashDumpDriverError::InvalidHeader);
+    }
+    let mut runs = Vec::with_capacity(number_of_runs);
+    let mut offset = DUMP_HEADER64_SIZE as u64;
+    for i in 0..number_of_runs {
+        let run = PHYSICAL_MEMORY_BLOCK_OFFSET + 16 + i * 16;
+        let base_page = read_u64(data, run)?;
+        let page_count = read_u64(data, run + 8)?;
+        let paddr = page_to_bytes(base_page)?;
+        let size = page_to_bytes(page_count)?;
+        if paddr.checked_add(size).is_none() {
+            return Err(CrashDumpDriverError::InvalidHeader);
+        }
+        runs.push(Run {
+            paddr,
+            offset,
+            size,
+        });
+        offset = offset
+            .checked_add(size)
+            .ok_or(CrashDumpDriverError::InvalidHeader)?;
+    }
+    if offset > data.len() as u64 {
+        return Err(CrashDumpDriverError::Truncated(data.len()));
+    }
+    Ok(runs)
+}
+
+/// parse the BMP_HEADER64 bitmap of a bitmap dump
+/// one bit per physical page, present pages are stored in order from FirstPage
+fn parse_bitmap_dump_runs(data: &[u8]) -> Result<Vec<Run>, CrashDumpDriverError> {
+    let bmp = DUMP_HEADER64_SIZE;
+    if data.get(bmp + 4..bmp + 8) != Some(&BMP_VALID_DUMP[..]) {
+        return Err(CrashDumpDriverError::InvalidHeader);
+    }
+    let first_page = read_u64(data, bmp + BMP_FIRST_PAGE_OFFSET)?;
+    let pages = read_u64(data, bmp + BMP_PAGES_OFFSET)?;
+    // every page must have an address
+    page_to_bytes(pages)?;
+    let bitmap_start = bmp + BMP_BITMAP_OFFSET;
+    let bitmap_len: usize = (pages / 8 + u64::from(pages % 8 != 0))
+        .try_into()
+        .map_err(|_| CrashDumpDriverError::InvalidHeader)?;
+    let bitmap = get_bytes(data, bitmap_start, bitmap_len)?;
+
+    // coalesce consecutive present pages into runs
+    let mut runs: Vec<Run> = Vec::new();
+    let mut offset = first_page;
+    for pfn in 0..pages {
+        if bitmap[(pfn / 8) as usize] & (1 << (pfn % 8)) == 0 {
+            continue;
+        }
+        // pfn < pages, which has been checked above
+        let paddr = pfn << PAGE_SHIFT;
+        match runs.last_mut() {
+            Some(last) if last.paddr + last.size == paddr => last.size += PAGE_SIZE as u64,
+            _ => runs.push(Run {
+                paddr,
+                offset,
+                size: PAGE_SIZE as u64,
+            }),
+        }
+        offset = offset
+            .checked_add(PAGE_SIZE as u64)
+            .ok_or(CrashDumpDriverError::InvalidHeader)?;
+    }
+    if offset > data.len() as u64 {
+        return Err(CrashDumpDriverError::Truncated(data.len()));
+    }
+    Ok(runs)
+}
+
+/// parse the x64 CONTEXT record
+fn parse_context(data: &[u8], cr3: u64) -> Result<X86Registers, CrashDumpDriverError> {
+    let ctx = CONTEXT_RECORD_OFFSET;
+    let selector = |offset: usize| -> Result<SegmentReg, CrashDumpDriverError> {
+        Ok(SegmentReg {
+            selector: read_u16(data, ctx + offset)?,
+            ..Default::default()
+        })
+    };
+    Ok(X86Registers {
+        cs: selector(0x38)?,
+        ds: selector(0x3A)?,
+        es: selector(0x3C)?,
+        fs: selector(0x3E)?,
+        gs: selector(0x40)?,
+        ss: selector(0x42)?,
+        rflags: read_u32(data, ctx + 0x44)? as u64,
+        rax: read_u64(data, ctx + 0x78)?,
+        rcx: read_u64(data, ctx + 0x80)?,
+        rdx: read_u64(data, ctx + 0x88)?,
+        rbx: read_u64(data, ctx + 0x90)?,
+        rsp: read_u64(data, ctx + 0x98)?,
+        rbp: read_u64(data, ctx + 0xA0)?,
+        rsi: read_u64(data, ctx + 0xA8)?,
+        rdi: read_u64(data, ctx + 0xB0)?,
+        r8: read_u64(data, ctx + 0xB8)?,
+        r9: read_u64(data, ctx + 0xC0)?,
+        r10: read_u64(data, ctx + 0xC8)?,
+        r11: read_u64(data, ctx + 0xD0)?,
+        r12: read_u64(data, ctx + 0xD8)?,
+        r13: read_u64(data, ctx + 0xE0)?,
+        r14: read_u64(data, ctx + 0xE8)?,
+        r15: read_u64(data, ctx + 0xF0)?,
+        rip: read_u64(data, ctx + 0xF8)?,
+        // the CONTEXT record doesn't store the control registers, the header has CR3
+        // and the others are implied by the dump being a 64 bits one
+        cr0: CR0_PG | CR0_PE,
+        cr3,
+        cr4: CR4_PAE,
+        msr_efer: EFER_LMA | EFER_LME,
+        ..Default::default()
+    })
+}
+
+impl CrashDump {
+    pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
+        let file_params = init_params.file.ok_or(CrashDumpDriverError::MissingPath)?;
+        debug!("init on {}", file_params.path);
+        let file = File::open(&file_params.path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+        if mmap.len() < DUMP_HEADER64_SIZE
+            || &mmap[0..4] != DUMP_SIGNATURE
+            || &mmap[4..8] != DUMP_VALID_DUMP64
+        {
+            return Err(Box::new(CrashDumpDriverError::InvalidHeader));
+        }
+        let dump_type = read_u32(&mmap, DUMP_TYPE_OFFSET)?;
+        let mut runs = match dump_type {
+            DUMP_TYPE_FULL => parse_full_dump_runs(&mmap)?,
+            DUMP_TYPE_SUMMARY
+            | DUMP_TYPE_BITMAP
+            | DUMP_TYPE_BITMAP_KERNEL
+            | DUMP_TYPE_KERNEL_MEMORY
+            | DUMP_TYPE_KERNEL_AND_USER_MEMORY
+            | DUMP_TYPE_COMPLETE_MEMORY => parse_bitmap_dump_runs(&mmap)?,
+            _ => {
+                return Err(Box::new(CrashDumpDriverError::UnsupportedDumpType(
+                    dump_type,
+                )))
+            }
+        };
+        runs.sort_by_key(|r| r.paddr);
+        let vcpu_count = read_u32(&mmap, NUMBER_PROCESSORS_OFFSET)?.try_into()?;
+        let cr3 = read_u64(&mmap, DIRECTORY_TABLE_BASE_OFFSET)?;
+        let context = parse_context(&mmap, cr3)?;
+        debug!(
+            "crash dump type {:#X}: {} runs, {} vcpus",
+            dump_type,
+            runs.len(),
+            vcpu_count
+        );
+        Ok(CrashDump {
+            mmap,
+            runs,
+            vcpu_count,
+            context,
+        })
+    }
+
+    /// find the run containing the given physical address
+    fn find_run(&self, paddr: u64) -> Option<&Run> {
+        // index of the first run starting after paddr
+        let index = self.runs.partition_point(|r| r.paddr <= paddr);
+        if index == 0 {
+            return None;
+        }
+        let run = &self.runs[index - 1];
+        if paddr - run.paddr < run.size {
+            Some(run)
+        } else {
+            None
+        }
+    }
+}
+
+impl Introspectable for CrashDump {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::VCPU_COUNT
+                | Operations::READ_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::READ_REGISTERS
+                | Operations::PAUSE
+                | Operations::RESUME,
+            ..Default::default()
+        }
+    }
+
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
+        Ok(self.vcpu_count)
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        *bytes_read = 0;
+        let mut buf_offset: usize = 0;
+        while buf_offset < buf.len() {
+            let cur_paddr = paddr
+                .checked_add(buf_offset as u64)
+                .ok_or(CrashDumpDriverError::UnmappedAddress(paddr))?;
+            let run = self
+                .find_run(cur_paddr)
+                .ok_or(CrashDumpDriverError::UnmappedAddress(cur_paddr))?;
+            // determine how much we can read from this run
+            let run_offset = cur_paddr - run.paddr;
+            let read_len =
+                std::cmp::min((buf.len() - buf_offset) as u64, run.size - run_offset) as usize;
+            let file_start = (run.offset + run_offset) as usize;
+            buf[buf_offset..buf_offset + read_len]
+                .copy_from_slice(&self.mmap[file_start..file_start + read_len]);
+            buf_offset += read_len;
+            *bytes_read += read_len as u64;
+        }
+        Ok(())
+    }
+
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Ok(self.runs.last().map_or(0, |r| r.paddr + r.size))
+    }
+
+    fn get_memory_map(&self) -> Result<Vec<MemoryRange>, MicrovmiError> {
+        Ok(self
+            .runs
+            .iter()
+            .map(|r| MemoryRange {
+                start: r.paddr,
+                end: r.paddr + r.size,
+            })
+            .collect())
+    }
+
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
+        // only the context of the processor which bugchecked is stored in the header
+        if vcpu != 0 {
+            return Err(CrashDumpDriverError::MissingContext(vcpu).into());
+        }
+        Ok(Registers::X86(self.context.clone()))
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        // a memory dump is never running
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::CrashDump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::api::memory::PagingMode;
+    use crate::api::params::FileInitParams;
+    use crate::driver::temp_path;
+
+    use super::*;
+
+    fn create_header(dump_type: u32) -> Vec<u8> {
+        let mut dump = vec![0u8; DUMP_HEADER64_SIZE];
+        dump[0..4].copy_from_slice(DUMP_SIGNATURE);
+        dump[4..8].copy_from_slice(DUMP_VALID_DUMP64);
+        dump[DIRECTORY_TABLE_BASE_OFFSET..DIRECTORY_TABLE_BASE_OFFSET + 8]
+            .copy_from_slice(&0x1ab000u64.to_le_bytes());
+        dump[NUMBER_PROCESSORS_OFFSET..NUMBER_PROCESSORS_OFFSET + 4]
+            .copy_from_slice(&2u32.to_le_bytes());
+        // context rip
+        let rip = CONTEXT_RECORD_OFFSET + 0xF8;
+        dump[rip..rip + 8].copy_from_slice(&0xfffff80012345678u64.to_le_bytes());
+        dump[DUMP_TYPE_OFFSET..DUMP_TYPE_OFFSET + 4].copy_from_slice(&dump_type.to_le_bytes());
+        dump
+    }
+
+    fn write_dump(name: &str, content: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn init_params(path: &Path) -> DriverInitParams {
+        DriverInitParams {
+            file: Some(FileInitParams {
+                path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_full_dump() {
+        let mut dump = create_header(DUMP_TYPE_FULL);
+        // 2 runs: pfn 1, pfn 3-4
+        let block = PHYSICAL_MEMORY_BLOCK_OFFSET;
+        dump[block..block + 4].copy_from_slice(&2u32.to_le_bytes());
+        for (i, (base_page, page_count)) in [(1u64, 1u64), (3, 2)].iter().enumerate() {
+            let run = block + 16 + i * 16;
+            dump[run..run + 8].copy_from_slice(&base_page.to_le_bytes());
+            dump[run + 8..run + 16].copy_from_slice(&page_count.to_le_bytes());
+        }
+        for page in 1..=3u8 {
+            dump.extend_from_slice(&[page; PAGE_SIZE as usize]);
+        }
+        let path = write_dump("microvmi_test_full.dmp", &dump);
+        let drv = CrashDump::new(init_params(&path)).unwrap();
+        assert_eq!(5 * PAGE_SIZE as u64, drv.get_max_physical_addr().unwrap());
+        assert_eq!(2, drv.get_vcpu_count().unwrap());
+
+        let mut buf = [0u8; 2];
+        let mut bytes_read = 0;
+        // read starting in a run and ending in a hole
+        assert!(drv
+            .read_physical(0x4FFF, &mut buf, &mut bytes_read)
+            .is_err());
+        assert_eq!(1, bytes_read);
+        drv.read_physical(0x3FFF, &mut buf, &mut bytes_read)
+            .unwrap();
+        assert_eq!([2, 3], buf);
+        // pfn 2 is a hole
+        assert!(drv
+            .read_physical(0x2000, &mut buf, &mut bytes_read)
+            .is_err());
+
+        let Registers::X86(regs) = drv.read_registers(0).unwrap();
+        assert_eq!(0xfffff80012345678, regs.rip);
+        assert_eq!(0x1ab000, regs.cr3);
+        assert_eq!(Some(PagingMode::Level4), PagingMode::from_registers(&regs));
+        assert!(drv.read_registers(1).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    fn create_bitmap_dump(dump_type: u32) -> Vec<u8> {
+        let mut dump = create_header(dump_type);
+        // present pages: pfn 0, 1 and 7
+        let mut bmp = vec![0u8; BMP_BITMAP_OFFSET];
+        bmp[0..4].copy_from_slice(b"SDMP");
+        bmp[4..8].copy_from_slice(BMP_VALID_DUMP);
+        let first_page = (DUMP_HEADER64_SIZE + PAGE_SIZE as usize) as u64;
+        bmp[BMP_FIRST_PAGE_OFFSET..BMP_FIRST_PAGE_OFFSET + 8]
+            .copy_from_slice(&first_page.to_le_bytes());
+        bmp[BMP_PAGES_OFFSET..BMP_PAGES_OFFSET + 8].copy_from_slice(&8u64.to_le_bytes());
+        bmp.push(0b1000_0011);
+        bmp.resize(PAGE_SIZE as usize, 0);
+        dump.extend_from_slice(&bmp);
+        for page in 1..=3u8 {
+            dump.extend_from_slice(&[page; PAGE_SIZE as usize]);
+        }
+        dump
+    }
+
+    #[test]
+    fn test_bitmap_dump() {
+        let dump = create_bitmap_dump(DUMP_TYPE_BITMAP);
+        let path = write_dump("microvmi_test_bitmap.dmp", &dump);
+        let drv = CrashDump::new(init_params(&path)).unwrap();
+        assert_eq!(8 * PAGE_SIZE as u64, drv.get_max_physical_addr().unwrap());
+        // consecutive present pages are coalesced
+        assert_eq!(
+            vec![
+                MemoryRange {
+                    start: 0,
+                    end: 0x2000
+                },
+                MemoryRange {
+                    start: 0x7000,
+                    end: 0x8000
+                }
+            ],
+            drv.get_memory_map().unwrap()
+        );
+
+        let mut buf = [0u8; 2];
+        let mut bytes_read = 0;
+        drv.read_physical(0xFFF, &mut buf, &mut bytes_read).unwrap();
+        assert_eq!([1, 2], buf);
+        drv.read_physical(0x7000, &mut buf, &mut bytes_read)
+            .unwrap();
+        assert_eq!([3, 3], buf);
+        assert!(drv
+            .read_physical(0x2000, &mut buf, &mut bytes_read)
+            .is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_kernel_bitmap_dump() {
+        // summary and kernel bitmap dumps share the BMP_HEADER64 layout
+        for (name, dump_type) in [
+            ("microvmi_test_summary.dmp", DUMP_TYPE_SUMMARY),
+            ("microvmi_test_kernel_bitmap.dmp", DUMP_TYPE_BITMAP_KERNEL),
+        ]
+        .iter()
+        {
+            let path = write_dump(name, &create_bitmap_dump(*dump_type));
+            let drv = CrashDump::new(init_params(&path)).unwrap();
+            assert_eq!(8 * PAGE_SIZE as u64, drv.get_max_physical_addr().unwrap());
+            let mut buf = [0u8; 2];
+            let mut bytes_read = 0;
+            drv.read_physical(0x7000, &mut buf, &mut bytes_read)
+                .unwrap();
+            assert_eq!([3, 3], buf);
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fail_to_create_crash_dump_driver_on_overflowing_runs() {
+        let mut dump = create_header(DUMP_TYPE_FULL);
+        let block = PHYSICAL_MEMORY_BLOCK_OFFSET;
+        dump[block..block + 4].copy_from_slice(&1u32.to_le_bytes());
+        let path = write_dump("microvmi_test_overflow.dmp", &dump);
+        let patch = |base_page: u64, page_count: u64| {
+            let mut dump = dump.clone();
+            dump[block + 16..block + 24].copy_from_slice(&base_page.to_le_bytes());
+            dump[block + 24..block + 32].copy_from_slice(&page_count.to_le_bytes());
+            fs::write(&path, dump).unwrap();
+            CrashDump::new(init_params(&path))
+        };
+        assert!(patch(u64::MAX, 1).is_err());
+        assert!(patch(0, u64::MAX).is_err());
+        assert!(patch(u64::MAX >> PAGE_SHIFT, 1).is_err());
+
+        // bitmap covering more pages than the physical address space
+        let mut dump = create_header(DUMP_TYPE_BITMAP);
+        let mut bmp = vec![0u8; BMP_BITMAP_OFFSET];
+        bmp[4..8].copy_from_slice(BMP_VALID_DUMP);
+        bmp[BMP_PAGES_OFFSET..BMP_PAGES_OFFSET + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        dump.extend_from_slice(&bmp);
+        fs::write(&path, dump).unwrap();
+        assert!(CrashDump::new(init_params(&path)).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,590 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+
+use memmap2::{Mmap, MmapOptions};
+
+use crate::api::params::DriverInitParams;
+use crate::api::registers::{Registers, SegmentReg, SystemTableReg, X86Registers};
+use crate::api::{Capabilities, DriverType, Introspectable, MemoryRange, Operations};
+use crate::errors::MicrovmiError;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+// e_phnum value signaling that the real count is stored in sh_info of section header 0
+const PN_XNUM: u16 = 0xFFFF;
+const NT_PRSTATUS: u32 = 1;
+// QEMU dump-guest-memory stores its own CPU state in a "QEMU" note of type 0
+const NT_QEMU: u32 = 0;
+
+const ELF64_PHDR_SIZE: usize = 56;
+// offset of pr_reg in x86_64 struct elf_prstatus
+const PRSTATUS_REGS_OFFSET: usize = 112;
+// 27 registers of x86_64 struct user_regs_struct
+const PRSTATUS_REGS_SIZE: usize = 27 * 8;
+// size of the x86_64 QEMUCPUState, up to cr[4] included
+const QEMU_CPU_STATE_SIZE: usize = 432;
+const QEMU_CPU_SEGMENT_SIZE: usize = 24;
+const CR0_PE: u64 = 1 << 0;
+const CR0_PG: u64 = 1 << 31;
+const CR4_PAE: u64 = 1 << 5;
+const EFER_LME: u64 = 1 << 8;
+const EFER_LMA: u64 = 1 << 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ElfCoreDriverError {
+    #[error("ELF core driver requires a path parameter")]
+    MissingPath,
+    #[error("not an ELF64 little-endian core file")]
+    InvalidHeader,
+    #[error("ELF core file is truncated at offset {0:#X}")]
+    Truncated(usize),
+    #[error("physical address {0:#X} is not mapped in the ELF core file")]
+    UnmappedAddress(u64),
+    #[error("no register state available for vcpu {0}")]
+    InvalidVcpu(u16),
+}
+
+impl From<ElfCoreDriverError> for MicrovmiError {
+    fn from(error: ElfCoreDriverError) -> Self {
+        match error {
+            ElfCoreDriverError::UnmappedAddress(paddr) => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// A PT_LOAD segment, mapping a guest physical range to a file range
+#[derive(Debug)]
+struct LoadSegment {
+    paddr: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// ELF core dump driver
+///
+/// Supports the dumps produced by `virsh dump --memory-only` and QEMU `dump-guest-memory`.
+pub struct ElfCore {
+    mmap: Mmap,
+    // sorted by physical address
+    segments: Vec<LoadSegment>,
+    // one entry per VCPU
+    vcpus: Vec<X86Registers>,
+}
+
+/// get `len` bytes at `offset`, without overflowing on untrusted offsets
+fn get_bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], ElfCoreDriverError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(ElfCoreDriverError::Truncated(offset))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ElfCoreDriverError> {
+    get_bytes(data, offset, 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ElfCoreDriverError> {
+    get_bytes(data, offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, ElfCoreDriverError> {
+    get_bytes(data, offset, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn align4(value: usize) -> Option<usize> {
+    value.checked_add(3).map(|value| value & !3)
+}
+
+/// parse a QEMUCPUSegment
+fn parse_qemu_segment(desc: &[u8], offset: usize) -> Result<SegmentReg, ElfCoreDriverError> {
+    Ok(SegmentReg {
+        selector: read_u32(desc, offset)? as u16,
+        limit: read_u32(desc, offset + 4)?,
+        base: read_u64(desc, offset + 16)?,
+    })
+}
+
+/// parse the x86_64 QEMUCPUState from a "QEMU" note
+fn parse_qemu_cpu_state(desc: &[u8]) -> Result<X86Registers, ElfCoreDriverError> {
+    if desc.len() < QEMU_CPU_STATE_SIZE {
+        return Err(ElfCoreDriverError::Truncated(desc.len()));
+    }
+    // skip version and size fields
+    let gpr = |index: usize| read_u64(desc, 8 + index * 8);
+    let segment = |index: usize| parse_qemu_segment(desc, 152 + index * QEMU_CPU_SEGMENT_SIZE);
+    let cr = |index: usize| read_u64(desc, 392 + index * 8);
+    let gdt = segment(8)?;
+    let idt = segment(9)?;
+    Ok(X86Registers {
+        rax: gpr(0)?,
+        rbx: gpr(1)?,
+        rcx: gpr(2)?,
+        rdx: gpr(3)?,
+        rsi: gpr(4)?,
+        rdi: gpr(5)?,
+        rsp: gpr(6)?,
+        rbp: gpr(7)?,
+        r8: gpr(8)?,
+        r9: gpr(9)?,
+        r10: gpr(10)?,
+        r11: gpr(11)?,
+        r12: gpr(12)?,
+        r13: gpr(13)?,
+        r14: gpr(14)?,
+        r15: gpr(15)?,
+        rip: gpr(16)?,
+        rflags: gpr(17)?,
+        cs: segment(0)?,
+        ds: segment(1)?,
+        es: segment(2)?,
+        fs: segment(3)?,
+        gs: segment(4)?,
+        ss: segment(5)?,
+        ldt: segment(6)?,
+        tr: segment(7)?,
+        gdt: SystemTableReg {
+            base: gdt.base,
+            limit: gdt.limit as u16,
+        },
+        idt: SystemTableReg {
+            base: idt.base,
+            limit: idt.limit as u16,
+        },
+        cr0: cr(0)?,
+        cr2: cr(2)?,
+        cr3: cr(3)?,
+        cr4: cr(4)?,
+        ..Default::default()
+    })
+}
+
+/// parse the x86_64 user_regs_struct from a NT_PRSTATUS note
+fn parse_prstatus(desc: &[u8]) -> Result<X86Registers, ElfCoreDriverError> {
+    if desc.len() < PRSTATUS_REGS_OFFSET + PRSTATUS_REGS_SIZE {
+        return Err(ElfCoreDriverError::Truncated(desc.len()));
+    }
+    let reg = |index: usize| read_u64(desc, PRSTATUS_REGS_OFFSET + index * 8);
+    let selector = |index: usize| -> Result<SegmentReg, ElfCoreDriverError> {
+        Ok(SegmentReg {
+            selector: reg(index)? as u16,
+            ..Default::default()
+        })
+    };
+    Ok(X86Registers {
+        r15: reg(0)?,
+        r14: reg(1)?,
+        r13: reg(2)?,
+        r12: reg(3)?,
+        rbp: reg(4)?,
+        rbx: reg(5)?,
+        r11: reg(6)?,
+        r10: reg(7)?,
+        r9: reg(8)?,
+        r8: reg(9)?,
+        rax: reg(10)?,
+        rcx: reg(11)?,
+        rdx: reg(12)?,
+        rsi: reg(13)?,
+        rdi: reg(14)?,
+        // 15: orig_rax
+        rip: reg(16)?,
+        cs: selector(17)?,
+        rflags: reg(18)?,
+        rsp: reg(19)?,
+        ss: selector(20)?,
+        fs: SegmentReg {
+            base: reg(21)?,
+            selector: reg(25)? as u16,
+            ..Default::default()
+        },
+        gs: SegmentReg {
+            base: reg(22)?,
+            selector: reg(26)? as u16,
+            ..Default::default()
+        },
+        ds: selector(23)?,
+        es: selector(24)?,
+        ..Default::default()
+    })
+}
+
+impl ElfCore {
+    pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
+        let file_params = init_params.file.ok_or(ElfCoreDriverError::MissingPath)?;
+        debug!("init on {}", file_params.path);
+        let file = File::open(&file_params.path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+        // ELF header
+        if mmap.len() < 64
+            || &mmap[0..4] != ELF_MAGIC
+            || mmap[4] != ELFCLASS64
+            || mmap[5] != ELFDATA2LSB
+            || read_u16(&mmap, 0x10)? != ET_CORE
+        {
+            return Err(Box::new(ElfCoreDriverError::InvalidHeader));
+        }
+        let phoff: usize = read_u64(&mmap, 0x20)?.try_into()?;
+        let shoff: usize = read_u64(&mmap, 0x28)?.try_into()?;
+        let e_machine = read_u16(&mmap, 0x12)?;
+        let phnum = match read_u16(&mmap, 0x38)? {
+            // sh_info of section header 0
+            PN_XNUM => read_u32(get_bytes(&mmap, shoff, 64)?, 44)? as usize,
+            phnum => phnum as usize,
+        };
+
+        // program headers
+        let mut segments = Vec::new();
+        let mut qemu_states = Vec::new();
+        let mut prstatus_states = Vec::new();
+        let phdrs_len = phnum
+            .checked_mul(ELF64_PHDR_SIZE)
+            .ok_or(ElfCoreDriverError::InvalidHeader)?;
+        let phdrs = get_bytes(&mmap, phoff, phdrs_len)?;
+        for phdr in phdrs.chunks_exact(ELF64_PHDR_SIZE) {
+            let p_type = read_u32(phdr, 0)?;
+            let p_offset = read_u64(phdr, 8)?;
+            let p_paddr = read_u64(phdr, 24)?;
+            let p_filesz = read_u64(phdr, 32)?;
+            if p_offset.saturating_add(p_filesz) > mmap.len() as u64 {
+                return Err(Box::new(ElfCoreDriverError::Truncated(p_offset as usize)));
+            }
+            if p_paddr.checked_add(p_filesz).is_none() {
+                return Err(Box::new(ElfCoreDriverError::InvalidHeader));
+            }
+            match p_type {
+                // only the file backed part of the segment is available
+                // the rest (p_memsz > p_filesz) is reported as a hole
+                PT_LOAD if p_filesz > 0 => segments.push(LoadSegment {
+                    paddr: p_paddr,
+                    offset: p_offset,
+                    size: p_filesz,
+                }),
+                PT_NOTE => {
+                    let start = p_offset as usize;
+                    let notes = &mmap[start..start + p_filesz as usize];
+                    let mut offset = 0;
+                    while offset + 12 <= notes.len() {
+                        let namesz = read_u32(notes, offset)? as usize;
+                        let descsz = read_u32(notes, offset + 4)? as usize;
+                        let n_type = read_u32(notes, offset + 8)?;
+                        let name_start = offset + 12;
+                        let desc_start = align4(namesz)
+                            .and_then(|namesz| name_start.checked_add(namesz))
+                            .ok_or(ElfCoreDriverError::Truncated(start + name_start))?;
+                        let name = get_bytes(notes, name_start, namesz)
+                            .map_err(|_| ElfCoreDriverError::Truncated(start + name_start))?;
+                        let desc = get_bytes(notes, desc_start, descsz)
+                            .map_err(|_| ElfCoreDriverError::Truncated(start + desc_start))?;
+                        match (name, n_type) {
+                            (b"CORE\0", NT_PRSTATUS) => prstatus_states.push(parse_prstatus(desc)?),
+                            (b"QEMU\0", NT_QEMU) => qemu_states.push(parse_qemu_cpu_state(desc)?),
+                            _ => trace!("skipping note type {} ({} bytes)", n_type, descsz),
+                        }
+                        // desc is in bounds, the end can't overflow
+                        offset = align4(desc_start + descsz).unwrap_or(usize::MAX);
+                    }
+                }
+                _ => {}
+            }
+        }
+        segments.sort_by_key(|s| s.paddr);
+        // QEMU notes contain the control registers and segments, prefer them
+        let mut vcpus = if qemu_states.is_empty() {
+            // an x86_64 kernel runs with 4-level paging enabled
+            if e_machine == EM_X86_64 {
+                for regs in prstatus_states.iter_mut() {
+                    regs.cr0 = CR0_PG | CR0_PE;
+                    regs.cr4 = CR4_PAE;
+                }
+            }
+            prstatus_states
+        } else {
+            qemu_states
+        };
+        // neither note stores EFER, but QEMU only writes EM_X86_64 when the guest is in long mode
+        if e_machine == EM_X86_64 {
+            for regs in vcpus.iter_mut() {
+                regs.msr_efer = EFER_LMA | EFER_LME;
+            }
+        }
+        debug!(
+            "ELF core: {} load segments, {} vcpus",
+            segments.len(),
+            vcpus.len()
+        );
+        Ok(ElfCore {
+            mmap,
+            segments,
+            vcpus,
+        })
+    }
+
+    /// find the segment mapping the given physical address
+    fn find_segment(&self, paddr: u64) -> Option<&LoadSegment> {
+        // index of the first segment starting after paddr
+        let index = self.segments.partition_point(|s| s.paddr <= paddr);
+        if index == 0 {
+            return None;
+        }
+        let segment = &self.segments[index - 1];
+        if paddr - segment.paddr < segment.size {
+            Some(segment)
+        } else {
+            None
+        }
+    }
+}
+
+impl Introspectable for ElfCore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::VCPU_COUNT
+                | Operations::READ_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::READ_REGISTERS
+                | Operations::PAUSE
+                | Operations::RESUME,
+            ..Default::default()
+        }
+    }
+
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
+        Ok(self.vcpus.len().try_into()?)
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        *bytes_read = 0;
+        let mut buf_offset: usize = 0;
+        while buf_offset < buf.len() {
+            let cur_paddr = paddr
+                .checked_add(buf_offset as u64)
+                .ok_or(ElfCoreDriverError::UnmappedAddress(paddr))?;
+            let segment = self
+                .find_segment(cur_paddr)
+                .ok_or(ElfCoreDriverError::UnmappedAddress(cur_paddr))?;
+            // determine how much we can read from this segment
+            let segment_offset = cur_paddr - segment.paddr;
+            let read_len = std::cmp::min(
+                (buf.len() - buf_offset) as u64,
+                segment.size - segment_offset,
+            ) as usize;
+            let file_start = (segment.offset + segment_offset) as usize;
+            buf[buf_offset..buf_offset + read_len]
+                .copy_from_slice(&self.mmap[file_start..file_start + read_len]);
+            buf_offset += read_len;
+            *bytes_read += read_len as u64;
+        }
+        Ok(())
+    }
+
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Ok(self
+            .segments
+            .iter()
+            .map(|s| s.paddr + s.size)
+            .max()
+            .unwrap_or(0))
+    }
+
+    fn get_memory_map(&self) -> Result<Vec<MemoryRange>, MicrovmiError> {
+        Ok(self
+            .segments
+            .iter()
+            .map(|s| MemoryRange {
+                start: s.paddr,
+                end: s.paddr + s.size,
+            })
+            .collect())
+    }
+
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
+        let regs = self
+            .vcpus
+            .get(vcpu as usize)
+            .ok_or(ElfCoreDriverError::InvalidVcpu(vcpu))?;
+        Ok(Registers::X86(regs.clone()))
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        // a memory dump is never running
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::ElfCore
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::api::memory::PagingMode;
+    use crate::api::params::FileInitParams;
+    use crate::driver::temp_path;
+
+    use super::*;
+
+    fn push_phdr(elf: &mut Vec<u8>, p_type: u32, offset: u64, paddr: u64, size: u64) {
+        elf.extend_from_slice(&p_type.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        elf.extend_from_slice(&offset.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&paddr.to_le_bytes());
+        elf.extend_from_slice(&size.to_le_bytes());
+        elf.extend_from_slice(&size.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+    }
+
+    /// build a core file with 2 RAM ranges: [0x0-0x10[ and [0x20-0x30[, and a single QEMU vcpu note
+    fn create_core(name: &str) -> PathBuf {
+        let phoff: u64 = 64;
+        let notes_offset = phoff + 3 * ELF64_PHDR_SIZE as u64;
+        let mut note = Vec::new();
+        note.extend_from_slice(&5u32.to_le_bytes());
+        note.extend_from_slice(&(QEMU_CPU_STATE_SIZE as u32).to_le_bytes());
+        note.extend_from_slice(&NT_QEMU.to_le_bytes());
+        note.extend_from_slice(b"QEMU\0\0\0\0");
+        let mut state = vec![0u8; QEMU_CPU_STATE_SIZE];
+        // rax
+        state[8..16].copy_from_slice(&0x1122u64.to_le_bytes());
+        // cr0, cr3 and cr4
+        state[392..400].copy_from_slice(&(CR0_PG | CR0_PE).to_le_bytes());
+        state[416..424].copy_from_slice(&0x1ab000u64.to_le_bytes());
+        state[424..432].copy_from_slice(&CR4_PAE.to_le_bytes());
+        note.extend_from_slice(&state);
+        let ram_offset = notes_offset + note.len() as u64;
+
+        let mut elf = Vec::new();
+        elf.extend_from_slice(ELF_MAGIC);
+        elf.extend_from_slice(&[ELFCLASS64, ELFDATA2LSB, 1]);
+        elf.resize(0x10, 0);
+        elf.extend_from_slice(&ET_CORE.to_le_bytes());
+        elf.extend_from_slice(&EM_X86_64.to_le_bytes());
+        elf.resize(0x20, 0);
+        elf.extend_from_slice(&phoff.to_le_bytes());
+        elf.resize(0x38, 0);
+        elf.extend_from_slice(&3u16.to_le_bytes());
+        elf.resize(64, 0);
+        push_phdr(&mut elf, PT_NOTE, notes_offset, 0, note.len() as u64);
+        push_phdr(&mut elf, PT_LOAD, ram_offset, 0, 0x10);
+        push_phdr(&mut elf, PT_LOAD, ram_offset + 0x10, 0x20, 0x10);
+        elf.extend_from_slice(&note);
+        elf.extend((0..0x20).map(|i| i as u8));
+
+        let path = temp_path(name);
+        fs::write(&path, elf).unwrap();
+        path
+    }
+
+    fn init_params(path: &Path) -> DriverInitParams {
+        DriverInitParams {
+            file: Some(FileInitParams {
+                path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_read_physical_reports_holes() {
+        let path = create_core("microvmi_test_holes.elf");
+        let drv = ElfCore::new(init_params(&path)).unwrap();
+        assert_eq!(0x30, drv.get_max_physical_addr().unwrap());
+        assert_eq!(
+            vec![
+                MemoryRange {
+                    start: 0,
+                    end: 0x10
+                },
+                MemoryRange {
+                    start: 0x20,
+                    end: 0x30
+                }
+            ],
+            drv.get_memory_map().unwrap()
+        );
+
+        let mut buf = [0u8; 4];
+        let mut bytes_read = 0;
+        drv.read_physical(0x22, &mut buf, &mut bytes_read).unwrap();
+        assert_eq!([0x12, 0x13, 0x14, 0x15], buf);
+
+        // read starting in RAM and ending in the hole
+        let mut buf = [0u8; 4];
+        assert!(drv.read_physical(0xE, &mut buf, &mut bytes_read).is_err());
+        assert_eq!(2, bytes_read);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_registers_from_qemu_note() {
+        let path = create_core("microvmi_test_regs.elf");
+        let drv = ElfCore::new(init_params(&path)).unwrap();
+        assert_eq!(1, drv.get_vcpu_count().unwrap());
+        let Registers::X86(regs) = drv.read_registers(0).unwrap();
+        assert_eq!(0x1122, regs.rax);
+        assert_eq!(0x1ab000, regs.cr3);
+        assert_eq!(Some(PagingMode::Level4), PagingMode::from_registers(&regs));
+        assert!(drv.read_registers(1).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fail_to_create_elfcore_driver_on_raw_file() {
+        let path = temp_path("microvmi_test_not_elf.raw");
+        fs::write(&path, [0u8; 128]).unwrap();
+        assert!(ElfCore::new(init_params(&path)).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fail_to_create_elfcore_driver_on_malformed_headers() {
+        let path = create_core("microvmi_test_malformed.elf");
+        let elf = fs::read(&path).unwrap();
+        let patch = |offset: usize, bytes: &[u8]| {
+            let mut elf = elf.clone();
+            elf[offset..offset + bytes.len()].copy_from_slice(bytes);
+            fs::write(&path, elf).unwrap();
+            ElfCore::new(init_params(&path))
+        };
+        // program headers offset close to the end of the address space
+        assert!(patch(0x20, &(u64::MAX - 8).to_le_bytes()).is_err());
+        // PN_XNUM with a section headers offset close to the end of the address space
+        let mut header = (u64::MAX - 8).to_le_bytes().to_vec();
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&PN_XNUM.to_le_bytes());
+        assert!(patch(0x28, &header).is_err());
+        // note name size overflowing the note
+        let note_offset = 64 + 3 * ELF64_PHDR_SIZE;
+        assert!(patch(note_offset, &u32::MAX.to_le_bytes()).is_err());
+        // load segment wrapping around the physical address space
+        let load_paddr = 64 + ELF64_PHDR_SIZE + 24;
+        assert!(patch(load_paddr, &u64::MAX.to_le_bytes()).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+use crate::api::params::DriverInitParams;
+use crate::api::{Capabilities, DriverType, Introspectable, Operations};
+use crate::errors::MicrovmiError;
+
+/// file extensions recognized as raw physical memory dumps
+const RAW_EXTENSIONS: [&str; 3] = ["raw", "vmem", "mem"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum FileDriverError {
+    #[error("File driver requires a path parameter")]
+    MissingPath,
+    #[error("{0} is not a raw memory dump (expected extensions: .raw, .vmem, .mem)")]
+    UnsupportedExtension(String),
+    #[error("write_physical requires the file driver to be opened as writable")]
+    ReadOnly,
+    #[error("physical address {paddr:#X} (size {size:#X}) is beyond the dump end ({max_addr:#X})")]
+    OutOfBounds {
+        paddr: u64,
+        size: u64,
+        max_addr: u64,
+    },
+}
+
+impl From<FileDriverError> for MicrovmiError {
+    fn from(error: FileDriverError) -> Self {
+        match error {
+            FileDriverError::OutOfBounds { paddr, .. } => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            FileDriverError::ReadOnly => MicrovmiError::PermissionDenied {
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+enum Mapping {
+    ReadOnly(Mmap),
+    // refcell required because write_physical takes &self
+    CopyOnWrite(RefCell<MmapMut>),
+}
+
+/// Raw physical memory dump driver
+///
+/// The dump is a flat image of the guest physical memory: file offset == physical address.
+pub struct RawFile {
+    mapping: Mapping,
+}
+
+impl RawFile {
+    pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
+        let file_params = init_params.file.ok_or(FileDriverError::MissingPath)?;
+        let path = Path::new(&file_params.path);
+        let is_raw = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| {
+                RAW_EXTENSIONS
+                    .iter()
+                    .any(|raw_ext| raw_ext.eq_ignore_ascii_case(ext))
+            });
+        if !is_raw {
+            return Err(Box::new(FileDriverError::UnsupportedExtension(
+                file_params.path,
+            )));
+        }
+        debug!(
+            "init on {} (writable: {})",
+            file_params.path, file_params.writable
+        );
+        let file = File::open(path)?;
+        // the file is never modified: a private copy-on-write mapping is used for writes
+        let mapping = if file_params.writable {
+            Mapping::CopyOnWrite(RefCell::new(unsafe { MmapOptions::new().map_copy(&file)? }))
+        } else {
+            Mapping::ReadOnly(unsafe { MmapOptions::new().map(&file)? })
+        };
+        Ok(RawFile { mapping })
+    }
+
+    fn len(&self) -> u64 {
+        match &self.mapping {
+            Mapping::ReadOnly(mmap) => mmap.len() as u64,
+            Mapping::CopyOnWrite(mmap) => mmap.borrow().len() as u64,
+        }
+    }
+}
+
+impl Introspectable for RawFile {
+    fn capabilities(&self) -> Capabilities {
+        let mut operations = Operations::READ_PHYSICAL
+            | Operations::MAX_PHYSICAL_ADDR
+            | Operations::PAUSE
+            | Operations::RESUME;
+        if let Mapping::CopyOnWrite(_) = self.mapping {
+            operations |= Operations::WRITE_PHYSICAL;
+        }
+        Capabilities {
+            operations,
+            ..Default::default()
+        }
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        *bytes_read = 0;
+        let max_addr = self.len();
+        if paddr >= max_addr {
+            return Err(FileDriverError::OutOfBounds {
+                paddr,
+                size: buf.len() as u64,
+                max_addr,
+            }
+            .into());
+        }
+        // a read crossing the end of the dump is truncated
+        let start: usize = paddr.try_into()?;
+        let read_len = std::cmp::min(buf.len() as u64, max_addr - paddr) as usize;
+        match &self.mapping {
+            Mapping::ReadOnly(mmap) => {
+                buf[..read_len].copy_from_slice(&mmap[start..start + read_len])
+            }
+            Mapping::CopyOnWrite(mmap) => {
+                buf[..read_len].copy_from_slice(&mmap.borrow()[start..start + read_len])
+            }
+        }
+        *bytes_read = read_len as u64;
+        if read_len < buf.len() {
+            return Err(FileDriverError::OutOfBounds {
+                paddr,
+                size: buf.len() as u64,
+                max_addr,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
+        let mmap = match &self.mapping {
+            Mapping::ReadOnly(_) => return Err(FileDriverError::ReadOnly.into()),
+            Mapping::CopyOnWrite(mmap) => mmap,
+        };
+        let max_addr = self.len();
+        let size = buf.len() as u64;
+        if paddr.checked_add(size).map_or(true, |end| end > max_addr) {
+            return Err(FileDriverError::OutOfBounds {
+                paddr,
+                size,
+                max_addr,
+            }
+            .into());
+        }
+        let start = paddr as usize;
+        mmap.borrow_mut()[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Ok(self.len())
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        // a memory dump is never running
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::File
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::api::params::FileInitParams;
+    use crate::driver::temp_path;
+
+    use super::*;
+
+    fn create_dump(name: &str, content: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn init_params(path: &Path, writable: bool) -> DriverInitParams {
+        DriverInitParams {
+            file: Some(FileInitParams {
+                path: path.to_str().unwrap().to_string(),
+                writable,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fail_to_create_file_driver_with_unknown_extension() {
+        let path = create_dump("microvmi_test_unknown.bin", &[0; 16]);
+        let result = RawFile::new(init_params(&path, false));
+        assert!(result.is_err(), "Expected error, got ok instead!");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_physical() {
+        let content: Vec<u8> = (0..=255).collect();
+        let path = create_dump("microvmi_test_read.raw", &content);
+        let drv = RawFile::new(init_params(&path, false)).unwrap();
+        assert_eq!(256, drv.get_max_physical_addr().unwrap());
+
+        let mut buf = [0u8; 4];
+        let mut bytes_read = 0;
+        drv.read_physical(0x10, &mut buf, &mut bytes_read).unwrap();
+        assert_eq!(4, bytes_read);
+        assert_eq!([0x10, 0x11, 0x12, 0x13], buf);
+
+        // read crossing the end of the dump
+        let mut buf = [0u8; 4];
+        assert!(drv.read_physical(0xFE, &mut buf, &mut bytes_read).is_err());
+        assert_eq!(2, bytes_read);
+        assert_eq!([0xFE, 0xFF, 0, 0], buf);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_write_physical_is_copy_on_write() {
+        let path = create_dump("microvmi_test_write.vmem", &[0; 16]);
+        let read_only = RawFile::new(init_params(&path, false)).unwrap();
+        assert!(!read_only
+            .capabilities()
+            .operations
+            .contains(Operations::WRITE_PHYSICAL));
+        assert!(read_only.write_physical(0, &[0xCC]).is_err());
+
+        let drv = RawFile::new(init_params(&path, true)).unwrap();
+        assert!(drv
+            .capabilities()
+            .operations
+            .contains(Operations::WRITE_PHYSICAL));
+        drv.write_physical(4, &[0xCC, 0xCC]).unwrap();
+        let mut buf = [0u8; 8];
+        let mut bytes_read = 0;
+        drv.read_physical(0, &mut buf, &mut bytes_read).unwrap();
+        assert_eq!([0, 0, 0, 0, 0xCC, 0xCC, 0, 0], buf);
+        // the file on disk is left untouched
+        assert_eq!(vec![0; 16], fs::read(&path).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+}
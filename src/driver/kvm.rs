@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::From;
 use std::convert::TryFrom;
 use std::convert::TryInto;
@@ -9,14 +11,18 @@ use kvmi::constants::PAGE_SIZE;
 #[cfg(test)] // only needed for tests
 use kvmi::errors::KVMiError;
 use kvmi::{
-    kvm_dtable, kvm_regs, kvm_segment, KVMIntrospectable, KVMiCr, KVMiEvent, KVMiEventReply,
-    KVMiEventType, KVMiInterceptType, KVMiPageAccess, SocketType,
+    kvm_dtable, kvm_regs, kvm_segment, kvm_sregs, KVMIntrospectable, KVMiCr, KVMiEvent,
+    KVMiEventReply, KVMiEventType, KVMiInterceptType, KVMiMsrIndices, KVMiPageAccess, SocketType,
 };
+use nix::errno::Errno;
 
 use crate::api::events::{CrType, Event, EventReplyType, EventType, InterceptType};
 use crate::api::params::{DriverInitParams, KVMInitParams};
 use crate::api::registers::{Registers, SegmentReg, SystemTableReg, X86Registers};
-use crate::api::{Access, DriverType, Introspectable};
+use crate::api::{
+    Access, Capabilities, DriverType, Intercepts, Introspectable, Operations, PAGE_SHIFT,
+};
+use crate::errors::MicrovmiError;
 
 impl TryFrom<Access> for KVMiPageAccess {
     type Error = &'static str;
@@ -94,12 +100,73 @@ impl From<X86Registers> for kvm_regs {
     }
 }
 
+/// MSRs reported by KVMi along with the registers, in to_x86_registers order
+const KVMI_MSRS: [KVMiMsrIndices; 6] = [
+    KVMiMsrIndices::SysenterCs,
+    KVMiMsrIndices::SysenterEsp,
+    KVMiMsrIndices::SysenterEip,
+    KVMiMsrIndices::MsrEfer,
+    KVMiMsrIndices::MsrStar,
+    KVMiMsrIndices::MsrLstar,
+];
+
+/// Build the x86 registers from the KVM vcpu state
+///
+/// msrs holds the values of KVMI_MSRS.
+fn to_x86_registers(regs: &kvm_regs, sregs: &kvm_sregs, msrs: [u64; 6]) -> X86Registers {
+    X86Registers {
+        rax: regs.rax,
+        rbx: regs.rbx,
+        rcx: regs.rcx,
+        rdx: regs.rdx,
+        rsi: regs.rsi,
+        rdi: regs.rdi,
+        rsp: regs.rsp,
+        rbp: regs.rbp,
+        r8: regs.r8,
+        r9: regs.r9,
+        r10: regs.r10,
+        r11: regs.r11,
+        r12: regs.r12,
+        r13: regs.r13,
+        r14: regs.r14,
+        r15: regs.r15,
+        rip: regs.rip,
+        rflags: regs.rflags,
+        cr0: sregs.cr0,
+        cr2: sregs.cr2,
+        cr3: sregs.cr3,
+        cr4: sregs.cr4,
+        sysenter_cs: msrs[0],
+        sysenter_esp: msrs[1],
+        sysenter_eip: msrs[2],
+        msr_efer: msrs[3],
+        msr_star: msrs[4],
+        msr_lstar: msrs[5],
+        efer: sregs.efer,
+        apic_base: sregs.apic_base,
+        cs: sregs.cs.into(),
+        ds: sregs.ds.into(),
+        es: sregs.es.into(),
+        fs: sregs.fs.into(),
+        gs: sregs.gs.into(),
+        ss: sregs.ss.into(),
+        tr: sregs.tr.into(),
+        ldt: sregs.ldt.into(),
+        idt: sregs.idt.into(),
+        gdt: sregs.gdt.into(),
+    }
+}
+
 #[derive(Debug)]
 pub struct Kvm<T: KVMIntrospectable> {
     kvmi: T,
     expect_pause_ev: u32,
     // VCPU -> KVMiEvent
     vec_events: Vec<Option<KVMiEvent>>,
+    // GFN -> last access set, KVMi cannot query it (RWX by default)
+    // refcell required because set_page_access takes &self
+    page_access: RefCell<HashMap<u64, Access>>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -108,6 +175,38 @@ pub enum KVMDriverError {
     MissingVMName,
     #[error("KVM driver initialization requires an additional socket parameter")]
     MissingSocketParameter,
+    #[error("failed to access physical memory at {paddr:#X}")]
+    MemoryAccess {
+        paddr: u64,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("MSR {0:#X} is not reported by KVMi")]
+    UnsupportedMsr(u32),
+}
+
+impl From<KVMDriverError> for MicrovmiError {
+    fn from(error: KVMDriverError) -> Self {
+        let address = match &error {
+            KVMDriverError::MemoryAccess { paddr, source } => {
+                match source.raw_os_error().map(Errno::from_i32) {
+                    // the gpa is not backed by a memory slot
+                    Some(Errno::EINVAL) | Some(Errno::ENOENT) | Some(Errno::EFAULT) => Some(*paddr),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match address {
+            Some(address) => MicrovmiError::InvalidAddress {
+                address,
+                source: Box::new(error),
+            },
+            None => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
 }
 
 impl<T: KVMIntrospectable> Kvm<T> {
@@ -126,6 +225,7 @@ impl<T: KVMIntrospectable> Kvm<T> {
             kvmi,
             expect_pause_ev: 0,
             vec_events: Vec::new(),
+            page_access: RefCell::new(HashMap::new()),
         };
 
         // set vec_events size
@@ -147,7 +247,28 @@ impl<T: KVMIntrospectable> Kvm<T> {
 }
 
 impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
-    fn get_vcpu_count(&self) -> Result<u16, Box<dyn Error>> {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::VCPU_COUNT
+                | Operations::READ_PHYSICAL
+                | Operations::WRITE_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::READ_REGISTERS
+                | Operations::WRITE_REGISTERS
+                | Operations::READ_MSR
+                | Operations::GET_PAGE_ACCESS
+                | Operations::SET_PAGE_ACCESS
+                | Operations::PAUSE
+                | Operations::RESUME
+                | Operations::EVENTS,
+            intercepts: Intercepts::CR
+                | Intercepts::MSR
+                | Intercepts::BREAKPOINT
+                | Intercepts::PAGEFAULT,
+        }
+    }
+
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
         Ok(self.kvmi.get_vcpu_count()?.try_into()?)
     }
 
@@ -156,76 +277,49 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
         paddr: u64,
         buf: &mut [u8],
         bytes_read: &mut u64,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         // kvmi read_physical can only handle a 4K buf request
         // any buffer bigger than that will result in an IOError (KVM_EINVAL)
         // need to chunk the read in 4K
         for (i, chunk) in buf.chunks_mut(PAGE_SIZE).enumerate() {
             let offset = i * PAGE_SIZE;
             let cur_paddr = paddr + offset as u64;
-            self.kvmi.read_physical(cur_paddr, chunk)?;
+            self.kvmi
+                .read_physical(cur_paddr, chunk)
+                .map_err(|source| KVMDriverError::MemoryAccess {
+                    paddr: cur_paddr,
+                    source,
+                })?;
         }
         *bytes_read = buf.len() as u64;
         Ok(())
     }
 
-    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), Box<dyn Error>> {
-        Ok(self.kvmi.write_physical(paddr, buf)?)
+    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
+        Ok(self
+            .kvmi
+            .write_physical(paddr, buf)
+            .map_err(|source| KVMDriverError::MemoryAccess { paddr, source })?)
     }
 
-    fn get_max_physical_addr(&self) -> Result<u64, Box<dyn Error>> {
-        Ok(self.kvmi.get_maximum_paddr()?)
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        self.kvmi.get_maximum_paddr().map_err(MicrovmiError::other)
     }
 
-    fn read_registers(&self, vcpu: u16) -> Result<Registers, Box<dyn Error>> {
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
         let (regs, sregs, msrs) = self.kvmi.get_registers(vcpu)?;
-        let msrs_as_slice = msrs.as_slice();
+        // match the entries by index, rather than relying on their order
+        let mut msr_values = [0; 6];
+        for entry in msrs.as_slice() {
+            if let Some(i) = KVMI_MSRS.iter().position(|&msr| msr as u32 == entry.index) {
+                msr_values[i] = entry.data;
+            }
+        }
         // TODO: hardcoded for x86 for now
-        Ok(Registers::X86(X86Registers {
-            rax: regs.rax,
-            rbx: regs.rbx,
-            rcx: regs.rcx,
-            rdx: regs.rdx,
-            rsi: regs.rsi,
-            rdi: regs.rdi,
-            rsp: regs.rsp,
-            rbp: regs.rbp,
-            r8: regs.r8,
-            r9: regs.r9,
-            r10: regs.r10,
-            r11: regs.r11,
-            r12: regs.r12,
-            r13: regs.r13,
-            r14: regs.r14,
-            r15: regs.r15,
-            rip: regs.rip,
-            rflags: regs.rflags,
-            cr0: sregs.cr0,
-            cr2: sregs.cr2,
-            cr3: sregs.cr3,
-            cr4: sregs.cr4,
-            sysenter_cs: msrs_as_slice[0].data,
-            sysenter_esp: msrs_as_slice[1].data,
-            sysenter_eip: msrs_as_slice[2].data,
-            msr_efer: msrs_as_slice[3].data,
-            msr_star: msrs_as_slice[4].data,
-            msr_lstar: msrs_as_slice[5].data,
-            efer: sregs.efer,
-            apic_base: sregs.apic_base,
-            cs: sregs.cs.into(),
-            ds: sregs.ds.into(),
-            es: sregs.es.into(),
-            fs: sregs.fs.into(),
-            gs: sregs.gs.into(),
-            ss: sregs.ss.into(),
-            tr: sregs.tr.into(),
-            ldt: sregs.ldt.into(),
-            idt: sregs.idt.into(),
-            gdt: sregs.gdt.into(),
-        }))
-    }
-
-    fn write_registers(&self, vcpu: u16, reg: Registers) -> Result<(), Box<dyn Error>> {
+        Ok(Registers::X86(to_x86_registers(&regs, &sregs, msr_values)))
+    }
+
+    fn write_registers(&self, vcpu: u16, reg: Registers) -> Result<(), MicrovmiError> {
         match reg {
             Registers::X86(x86_registers) => {
                 self.kvmi.set_registers(vcpu, &x86_registers.into())?;
@@ -234,12 +328,35 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
         Ok(())
     }
 
-    fn set_page_access(&self, paddr: u64, access: Access) -> Result<(), Box<dyn Error>> {
-        self.kvmi.set_page_access(paddr, access.try_into()?, 0)?;
+    /// Only the MSRs of KVMI_MSRS can be read, and none written
+    fn read_msr(&self, vcpu: u16, index: u32) -> Result<u64, MicrovmiError> {
+        let (_, _, msrs) = self.kvmi.get_registers(vcpu)?;
+        let entry = msrs
+            .as_slice()
+            .iter()
+            .find(|entry| entry.index == index)
+            .ok_or(KVMDriverError::UnsupportedMsr(index))?;
+        Ok(entry.data)
+    }
+
+    fn get_page_access(&self, paddr: u64) -> Result<Access, MicrovmiError> {
+        Ok(*self
+            .page_access
+            .borrow()
+            .get(&(paddr >> PAGE_SHIFT))
+            .unwrap_or(&Access::RWX))
+    }
+
+    fn set_page_access(&self, paddr: u64, access: Access) -> Result<(), MicrovmiError> {
+        let kvmi_access: KVMiPageAccess = access.try_into().map_err(MicrovmiError::other)?;
+        self.kvmi.set_page_access(paddr, kvmi_access, 0)?;
+        self.page_access
+            .borrow_mut()
+            .insert(paddr >> PAGE_SHIFT, access);
         Ok(())
     }
 
-    fn pause(&mut self) -> Result<(), Box<dyn Error>> {
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
         debug!("pause");
         // already paused ?
         if self.expect_pause_ev > 0 {
@@ -252,9 +369,9 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
         Ok(())
     }
 
-    fn resume(&mut self) -> Result<(), Box<dyn Error>> {
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
         debug!("resume");
-        self.kvmi.resume()?;
+        self.kvmi.resume().map_err(MicrovmiError::other)?;
         Ok(())
     }
 
@@ -263,7 +380,7 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
         vcpu: u16,
         intercept_type: InterceptType,
         enabled: bool,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         match intercept_type {
             InterceptType::Cr(micro_cr_type) => {
                 let kvmi_cr = match micro_cr_type {
@@ -286,16 +403,32 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
                     .kvmi
                     .control_events(vcpu, KVMiInterceptType::Pagefault, enabled)?)
             }
+            // not exposed by kvmi
+            InterceptType::Singlestep => Err(MicrovmiError::Unsupported("Singlestep intercept")),
         }
     }
 
-    fn listen(&mut self, timeout: u32) -> Result<Option<Event>, Box<dyn Error>> {
+    fn listen(&mut self, timeout: u32) -> Result<Option<Event>, MicrovmiError> {
         // wait for next event and pop it
         debug!("wait for next event");
         let kvmi_event_opt = self.kvmi.wait_and_pop_event(timeout.try_into()?)?;
         match kvmi_event_opt {
             None => Ok(None),
             Some(kvmi_event) => {
+                // the event carries the vcpu registers at the time of the event
+                let arch = unsafe { &(*kvmi_event.ffi_event).event.common.arch };
+                let regs = Registers::X86(to_x86_registers(
+                    &arch.regs,
+                    &arch.sregs,
+                    [
+                        arch.msrs.sysenter_cs,
+                        arch.msrs.sysenter_esp,
+                        arch.msrs.sysenter_eip,
+                        arch.msrs.efer,
+                        arch.msrs.star,
+                        arch.msrs.lstar,
+                    ],
+                ));
                 let microvmi_event_kind = match kvmi_event.ev_type {
                     KVMiEventType::Cr { cr_type, new, old } => EventType::Cr {
                         cr_type: match cr_type {
@@ -323,12 +456,13 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
                 };
 
                 let vcpu = kvmi_event.vcpu;
-                let vcpu_index: usize = vcpu.try_into()?;
+                let vcpu_index = usize::from(vcpu);
                 self.vec_events[vcpu_index] = Some(kvmi_event);
 
                 Ok(Some(Event {
                     vcpu,
                     kind: microvmi_event_kind,
+                    regs: Some(regs),
                 }))
             }
         }
@@ -338,12 +472,39 @@ impl<T: KVMIntrospectable> Introspectable for Kvm<T> {
         &mut self,
         event: Event,
         reply_type: EventReplyType,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         let kvm_reply_type = match reply_type {
             EventReplyType::Continue => KVMiEventReply::Continue,
+            EventReplyType::Retry => KVMiEventReply::Retry,
+            EventReplyType::SetRegisters(regs) => {
+                self.write_registers(event.vcpu, regs)?;
+                KVMiEventReply::Continue
+            }
+            EventReplyType::SkipInstruction => {
+                let insn_len = match event.kind {
+                    EventType::Breakpoint { insn_len, .. } => insn_len,
+                    _ => return Err(MicrovmiError::Unsupported("SkipInstruction reply")),
+                };
+                let Registers::X86(mut regs) = match event.regs {
+                    Some(ref regs) => regs.clone(),
+                    None => self.read_registers(event.vcpu)?,
+                };
+                regs.rip += u64::from(insn_len);
+                self.write_registers(event.vcpu, Registers::X86(regs))?;
+                // continue would reinject the #BP
+                KVMiEventReply::Retry
+            }
+            // the faulting instruction is emulated when a pagefault event is continued
+            EventReplyType::Emulate => match event.kind {
+                EventType::Pagefault { .. } => KVMiEventReply::Continue,
+                _ => return Err(MicrovmiError::Unsupported("Emulate reply")),
+            },
+            EventReplyType::SingleStep => {
+                return Err(MicrovmiError::Unsupported("SingleStep reply"))
+            }
         };
         // get KVMiEvent associated with this VCPU
-        let vcpu_index: usize = event.vcpu.try_into()?;
+        let vcpu_index = usize::from(event.vcpu);
         let kvmi_event = mem::replace(&mut self.vec_events[vcpu_index], None).unwrap();
         Ok(self.kvmi.reply(&kvmi_event, kvm_reply_type)?)
     }
@@ -375,7 +536,7 @@ impl<T: KVMIntrospectable> Drop for Kvm<T> {
 mod tests {
     use std::fmt::{Debug, Formatter};
 
-    use kvmi::{kvm_regs, kvm_sregs, KvmMsrs};
+    use kvmi::{kvm_regs, kvm_sregs, kvmi_dom_event, KvmMsrs};
     use mockall::mock;
     use mockall::predicate::{eq, function};
     use test_case::test_case;
@@ -410,6 +571,230 @@ mod tests {
         assert!(result.is_err(), "Expected error, got ok instead!");
     }
 
+    #[test]
+    fn test_get_page_access_returns_last_access_set() {
+        let mut kvmi_mock = MockKVMi::default();
+        kvmi_mock.expect_init().returning(|_| Ok(()));
+        kvmi_mock.expect_get_vcpu_count().returning(|| Ok(1));
+        kvmi_mock
+            .expect_control_events()
+            .returning(|_, _, _| Ok(()));
+        kvmi_mock
+            .expect_set_page_access()
+            .with(
+                eq(0x2000),
+                function(|x| matches!(x, KVMiPageAccess::R)),
+                eq(0),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        kvmi_mock
+            .expect_set_page_access()
+            .with(
+                eq(0x3000),
+                function(|x| matches!(x, KVMiPageAccess::NIL)),
+                eq(0),
+            )
+            .times(1)
+            .returning(|_, _, _| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "something went wrong",
+                ))
+            });
+
+        let kvm = Kvm::new(
+            kvmi_mock,
+            DriverInitParams {
+                common: Some(CommonInitParams {
+                    vm_name: String::from("some_vm"),
+                }),
+                kvm: Some(KVMInitParams::UnixSocket {
+                    path: "/tmp/introspector".to_string(),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(Access::RWX, kvm.get_page_access(0x2000).unwrap());
+        kvm.set_page_access(0x2000, Access::R).unwrap();
+        assert_eq!(Access::R, kvm.get_page_access(0x2FFF).unwrap());
+        assert_eq!(Access::RWX, kvm.get_page_access(0x1000).unwrap());
+        // a failed update keeps the previous access
+        assert!(kvm.set_page_access(0x3000, Access::NIL).is_err());
+        assert_eq!(Access::RWX, kvm.get_page_access(0x3000).unwrap());
+    }
+
+    #[test]
+    fn test_read_physical_classifies_errors() {
+        let mut kvmi_mock = MockKVMi::default();
+        kvmi_mock.expect_init().returning(|_| Ok(()));
+        kvmi_mock.expect_get_vcpu_count().returning(|| Ok(1));
+        kvmi_mock
+            .expect_control_events()
+            .returning(|_, _, _| Ok(()));
+        kvmi_mock.expect_read_physical().returning(|gpa, _| {
+            Err(std::io::Error::from_raw_os_error(if gpa == 0x1000 {
+                Errno::EINVAL as i32
+            } else {
+                Errno::EPIPE as i32
+            }))
+        });
+
+        let kvm = Kvm::new(
+            kvmi_mock,
+            DriverInitParams {
+                common: Some(CommonInitParams {
+                    vm_name: String::from("some_vm"),
+                }),
+                kvm: Some(KVMInitParams::UnixSocket {
+                    path: "/tmp/introspector".to_string(),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut buf = [0u8; 8];
+        let mut bytes_read = 0;
+        assert!(matches!(
+            kvm.read_physical(0x1000, &mut buf, &mut bytes_read),
+            Err(MicrovmiError::InvalidAddress {
+                address: 0x1000,
+                ..
+            })
+        ));
+        // the connection with the introspection socket is lost
+        assert!(matches!(
+            kvm.read_physical(0x2000, &mut buf, &mut bytes_read),
+            Err(MicrovmiError::Other { .. })
+        ));
+    }
+
+    /// build a KVMi event, allocated like libkvmi does since it is freed on drop
+    fn create_kvmi_event(vcpu: u16, ev_type: KVMiEventType, rip: u64) -> KVMiEvent {
+        let ffi_event = unsafe {
+            nix::libc::calloc(1, mem::size_of::<kvmi_dom_event>()) as *mut kvmi_dom_event
+        };
+        let arch = unsafe { &mut (*ffi_event).event.common.arch };
+        arch.regs.rip = rip;
+        arch.sregs.cr3 = 0x1ab000;
+        arch.msrs.efer = 0xd01;
+        KVMiEvent {
+            vcpu,
+            ev_type,
+            ffi_event,
+        }
+    }
+
+    #[test]
+    fn test_read_msr() {
+        let mut kvmi_mock = MockKVMi::default();
+        kvmi_mock.expect_init().returning(|_| Ok(()));
+        kvmi_mock.expect_get_vcpu_count().returning(|| Ok(1));
+        kvmi_mock
+            .expect_control_events()
+            .returning(|_, _, _| Ok(()));
+        kvmi_mock.expect_get_registers().returning(|_| {
+            let mut msrs = KvmMsrs::new();
+            // the entries are not in KVMI_MSRS order
+            msrs.as_slice_mut()[0].index = KVMiMsrIndices::MsrLstar as u32;
+            msrs.as_slice_mut()[0].data = 0xfffff80000001000;
+            msrs.as_slice_mut()[5].index = KVMiMsrIndices::SysenterCs as u32;
+            msrs.as_slice_mut()[5].data = 0x10;
+            Ok((kvm_regs::default(), kvm_sregs::default(), msrs))
+        });
+
+        let kvm = Kvm::new(
+            kvmi_mock,
+            DriverInitParams {
+                common: Some(CommonInitParams {
+                    vm_name: String::from("some_vm"),
+                }),
+                kvm: Some(KVMInitParams::UnixSocket {
+                    path: "/tmp/introspector".to_string(),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(0xfffff80000001000, kvm.read_msr(0, 0xc0000082).unwrap());
+        assert_eq!(0x10, kvm.read_msr(0, 0x174).unwrap());
+        // IA32_KERNEL_GS_BASE
+        assert!(kvm.read_msr(0, 0xc0000102).is_err());
+        assert!(matches!(
+            kvm.write_msr(0, 0x174, 0),
+            Err(MicrovmiError::Unsupported("write_msr"))
+        ));
+    }
+
+    #[test]
+    fn test_listen_reports_event_registers() {
+        let mut kvmi_mock = MockKVMi::default();
+        kvmi_mock.expect_init().returning(|_| Ok(()));
+        kvmi_mock.expect_get_vcpu_count().returning(|| Ok(1));
+        kvmi_mock
+            .expect_control_events()
+            .returning(|_, _, _| Ok(()));
+        let mut events = vec![
+            create_kvmi_event(
+                0,
+                KVMiEventType::Breakpoint {
+                    gpa: 0x5000,
+                    insn_len: 1,
+                },
+                0xfffff80000005000,
+            ),
+            create_kvmi_event(
+                0,
+                KVMiEventType::Cr {
+                    cr_type: KVMiCr::Cr3,
+                    new: 0x1ab000,
+                    old: 0x1aa000,
+                },
+                0xfffff80000001000,
+            ),
+        ];
+        // the registers must come from the event, not from the current vcpu state
+        kvmi_mock.expect_get_registers().never();
+        kvmi_mock
+            .expect_wait_and_pop_event()
+            .returning_st(move |_| Ok(events.pop()));
+
+        let mut kvm = Kvm::new(
+            kvmi_mock,
+            DriverInitParams {
+                common: Some(CommonInitParams {
+                    vm_name: String::from("some_vm"),
+                }),
+                kvm: Some(KVMInitParams::UnixSocket {
+                    path: "/tmp/introspector".to_string(),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let event = kvm.listen(0).unwrap().unwrap();
+        assert!(matches!(event.kind, EventType::Cr { new: 0x1ab000, .. }));
+        let Registers::X86(regs) = event.regs.unwrap();
+        assert_eq!(0xfffff80000001000, regs.rip);
+        assert_eq!(0x1ab000, regs.cr3);
+        assert_eq!(0xd01, regs.msr_efer);
+
+        let event = kvm.listen(0).unwrap().unwrap();
+        assert!(matches!(
+            event.kind,
+            EventType::Breakpoint { gpa: 0x5000, .. }
+        ));
+        let Registers::X86(regs) = event.regs.unwrap();
+        assert_eq!(0xfffff80000005000, regs.rip);
+        assert_eq!(0x1ab000, regs.cr3);
+        assert_eq!(0xd01, regs.msr_efer);
+    }
+
     #[test_case(1; "single vcpu")]
     #[test_case(2; "two vcpus")]
     #[test_case(16; "sixteen vcpus")]
@@ -0,0 +1,308 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+
+use memmap2::{Mmap, MmapOptions};
+
+use crate::api::params::DriverInitParams;
+use crate::api::{Capabilities, DriverType, Introspectable, MemoryRange, Operations};
+use crate::errors::MicrovmiError;
+
+// "EMiL" in little-endian
+const LIME_MAGIC: u32 = 0x4C69_4D45;
+const LIME_VERSION: u32 = 1;
+const LIME_HEADER_SIZE: usize = 32;
+
+#[derive(thiserror::Error, Debug)]
+pub enum LimeDriverError {
+    #[error("LiME driver requires a path parameter")]
+    MissingPath,
+    #[error("invalid LiME range header at offset {0:#X}")]
+    InvalidHeader(usize),
+    #[error("unsupported LiME version {0}")]
+    UnsupportedVersion(u32),
+    #[error("LiME file is truncated at offset {0:#X}")]
+    Truncated(usize),
+    #[error("physical address {0:#X} is not mapped in the LiME file")]
+    UnmappedAddress(u64),
+}
+
+impl From<LimeDriverError> for MicrovmiError {
+    fn from(error: LimeDriverError) -> Self {
+        match error {
+            LimeDriverError::UnmappedAddress(paddr) => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// A LiME range, mapping a guest physical range to a file range
+#[derive(Debug)]
+struct Range {
+    paddr: u64,
+    offset: u64,
+    size: u64,
+}
+
+/// LiME (Linux Memory Extractor) dump driver
+pub struct Lime {
+    mmap: Mmap,
+    // sorted by physical address
+    ranges: Vec<Range>,
+}
+
+fn get_bytes(data: &[u8], offset: usize, len: usize) -> Result<&[u8], LimeDriverError> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(LimeDriverError::Truncated(offset))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, LimeDriverError> {
+    get_bytes(data, offset, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, LimeDriverError> {
+    get_bytes(data, offset, 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+impl Lime {
+    pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
+        let file_params = init_params.file.ok_or(LimeDriverError::MissingPath)?;
+        debug!("init on {}", file_params.path);
+        let file = File::open(&file_params.path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+
+        // the file is a sequence of range headers, each followed by the range content
+        let mut ranges = Vec::new();
+        let mut offset: usize = 0;
+        while offset < mmap.len() {
+            let header = get_bytes(&mmap, offset, LIME_HEADER_SIZE)?;
+            if read_u32(header, 0)? != LIME_MAGIC {
+                return Err(Box::new(LimeDriverError::InvalidHeader(offset)));
+            }
+            let version = read_u32(header, 4)?;
+            if version != LIME_VERSION {
+                return Err(Box::new(LimeDriverError::UnsupportedVersion(version)));
+            }
+            // e_addr is inclusive
+            let s_addr = read_u64(header, 8)?;
+            let e_addr = read_u64(header, 16)?;
+            // the range end must be representable: ranges are [paddr, paddr + size)
+            let size = e_addr
+                .checked_add(1)
+                .filter(|_| e_addr >= s_addr)
+                .map(|end| end - s_addr)
+                .ok_or(LimeDriverError::InvalidHeader(offset))?;
+            let data_offset = offset + LIME_HEADER_SIZE;
+            let next_offset = size
+                .try_into()
+                .ok()
+                .and_then(|size: usize| data_offset.checked_add(size))
+                .filter(|next| *next <= mmap.len())
+                .ok_or(LimeDriverError::Truncated(data_offset))?;
+            trace!(
+                "LiME range {:#X}-{:#X} at {:#X}",
+                s_addr,
+                e_addr,
+                data_offset
+            );
+            ranges.push(Range {
+                paddr: s_addr,
+                offset: data_offset as u64,
+                size,
+            });
+            offset = next_offset;
+        }
+        if ranges.is_empty() {
+            return Err(Box::new(LimeDriverError::InvalidHeader(0)));
+        }
+        ranges.sort_by_key(|r| r.paddr);
+        debug!("LiME: {} ranges", ranges.len());
+        Ok(Lime { mmap, ranges })
+    }
+
+    /// find the range containing the given physical address
+    fn find_range(&self, paddr: u64) -> Option<&Range> {
+        // index of the first range starting after paddr
+        let index = self.ranges.partition_point(|r| r.paddr <= paddr);
+        if index == 0 {
+            return None;
+        }
+        let range = &self.ranges[index - 1];
+        if paddr - range.paddr < range.size {
+            Some(range)
+        } else {
+            None
+        }
+    }
+}
+
+impl Introspectable for Lime {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::READ_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::PAUSE
+                | Operations::RESUME,
+            ..Default::default()
+        }
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        *bytes_read = 0;
+        let mut buf_offset: usize = 0;
+        while buf_offset < buf.len() {
+            let cur_paddr = paddr
+                .checked_add(buf_offset as u64)
+                .ok_or(LimeDriverError::UnmappedAddress(paddr))?;
+            let range = self
+                .find_range(cur_paddr)
+                .ok_or(LimeDriverError::UnmappedAddress(cur_paddr))?;
+            // determine how much we can read from this range
+            let range_offset = cur_paddr - range.paddr;
+            let read_len =
+                std::cmp::min((buf.len() - buf_offset) as u64, range.size - range_offset) as usize;
+            let file_start = (range.offset + range_offset) as usize;
+            buf[buf_offset..buf_offset + read_len]
+                .copy_from_slice(&self.mmap[file_start..file_start + read_len]);
+            buf_offset += read_len;
+            *bytes_read += read_len as u64;
+        }
+        Ok(())
+    }
+
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        // ranges are sorted and never empty
+        let last = self.ranges.last().unwrap();
+        Ok(last.paddr + last.size)
+    }
+
+    fn get_memory_map(&self) -> Result<Vec<MemoryRange>, MicrovmiError> {
+        Ok(self
+            .ranges
+            .iter()
+            .map(|r| MemoryRange {
+                start: r.paddr,
+                end: r.paddr + r.size,
+            })
+            .collect())
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        // a memory dump is never running
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::LiME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::api::params::FileInitParams;
+    use crate::driver::temp_path;
+
+    use super::*;
+
+    fn push_range(lime: &mut Vec<u8>, s_addr: u64, content: &[u8]) {
+        lime.extend_from_slice(&LIME_MAGIC.to_le_bytes());
+        lime.extend_from_slice(&LIME_VERSION.to_le_bytes());
+        lime.extend_from_slice(&s_addr.to_le_bytes());
+        lime.extend_from_slice(&(s_addr + content.len() as u64 - 1).to_le_bytes());
+        lime.extend_from_slice(&[0; 8]);
+        lime.extend_from_slice(content);
+    }
+
+    fn create_lime(name: &str, content: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn init_params(path: &Path) -> DriverInitParams {
+        DriverInitParams {
+            file: Some(FileInitParams {
+                path: path.to_str().unwrap().to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_read_physical_across_ranges() {
+        let mut lime = Vec::new();
+        push_range(&mut lime, 0x1000, &[0xAA; 0x10]);
+        push_range(&mut lime, 0x1010, &[0xBB; 0x10]);
+        push_range(&mut lime, 0x2000, &[0xCC; 0x10]);
+        let path = create_lime("microvmi_test_ranges.lime", &lime);
+        let drv = Lime::new(init_params(&path)).unwrap();
+        assert_eq!(0x2010, drv.get_max_physical_addr().unwrap());
+
+        // contiguous ranges
+        let mut buf = [0u8; 4];
+        let mut bytes_read = 0;
+        drv.read_physical(0x100E, &mut buf, &mut bytes_read)
+            .unwrap();
+        assert_eq!([0xAA, 0xAA, 0xBB, 0xBB], buf);
+        assert_eq!(4, bytes_read);
+
+        // hole between 0x1020 and 0x2000
+        assert!(drv
+            .read_physical(0x101E, &mut buf, &mut bytes_read)
+            .is_err());
+        assert_eq!(2, bytes_read);
+        assert!(drv.read_physical(0, &mut buf, &mut bytes_read).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fail_to_create_lime_driver_on_truncated_file() {
+        let mut lime = Vec::new();
+        push_range(&mut lime, 0, &[0; 0x10]);
+        lime.truncate(lime.len() - 1);
+        let path = create_lime("microvmi_test_truncated.lime", &lime);
+        assert!(Lime::new(init_params(&path)).is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_fail_to_create_lime_driver_on_invalid_range() {
+        let mut lime = Vec::new();
+        push_range(&mut lime, 0x1000, &[0; 0x10]);
+        let path = create_lime("microvmi_test_invalid.lime", &lime);
+        let patch = |s_addr: u64, e_addr: u64| {
+            let mut lime = lime.clone();
+            lime[8..16].copy_from_slice(&s_addr.to_le_bytes());
+            lime[16..24].copy_from_slice(&e_addr.to_le_bytes());
+            fs::write(&path, lime).unwrap();
+            Lime::new(init_params(&path))
+        };
+        // end before start
+        assert!(patch(0x1000, 0xFFF).is_err());
+        // full address space
+        assert!(patch(0, u64::MAX).is_err());
+        // larger than the file
+        assert!(patch(0, u64::MAX - 1).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 use crate::api::params::{DriverInitParams, MemflowConnectorParams};
-use crate::api::{DriverType, Introspectable};
+use crate::api::{Capabilities, DriverType, Introspectable, MemoryRange, Operations};
+use crate::errors::MicrovmiError;
 use std::error::Error;
 
 use memflow::connector::{ConnectorArgs, ConnectorInstance, ConnectorInventory};
+use memflow::error::Error as MemflowError;
 use memflow::{PhysicalAddress, PhysicalMemory};
 use std::cell::RefCell;
 
@@ -12,6 +14,34 @@ pub enum MemflowDriverError {
     MissingConnectorParameter,
     #[error("Invalid format for Memflow connector argument (key=value), got {0}")]
     InvalidConnectorArgument(String),
+    #[error("failed to read physical memory at {paddr:#X}")]
+    PhysicalRead {
+        paddr: u64,
+        #[source]
+        source: MemflowError,
+    },
+}
+
+impl From<MemflowDriverError> for MicrovmiError {
+    fn from(error: MemflowDriverError) -> Self {
+        match error {
+            // out of the connector memory map, or rejected by the connector
+            MemflowDriverError::PhysicalRead {
+                paddr,
+                source: MemflowError::Bounds,
+            }
+            | MemflowDriverError::PhysicalRead {
+                paddr,
+                source: MemflowError::PhysicalMemory(_),
+            } => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
 }
 
 const QEMU_PROCFS_CONNECTOR_NAME: &str = "qemu_procfs";
@@ -74,23 +104,40 @@ impl Memflow {
 }
 
 impl Introspectable for Memflow {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::READ_PHYSICAL | Operations::MAX_PHYSICAL_ADDR,
+            ..Default::default()
+        }
+    }
+
     fn read_physical(
         &self,
         paddr: u64,
         buf: &mut [u8],
         bytes_read: &mut u64,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         self.connector
             .borrow_mut()
-            .phys_read_into(PhysicalAddress::from(paddr), buf)?;
+            .phys_read_into(PhysicalAddress::from(paddr), buf)
+            .map_err(|source| MemflowDriverError::PhysicalRead { paddr, source })?;
         *bytes_read = buf.len() as u64;
         Ok(())
     }
 
-    fn get_max_physical_addr(&self) -> Result<u64, Box<dyn Error>> {
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
         Ok(self.connector.borrow_mut().metadata().size as u64)
     }
 
+    fn get_memory_map(&self) -> Result<Vec<MemoryRange>, MicrovmiError> {
+        // memflow 0.1 connector metadata only carries the memory size,
+        // the connectors keep their memory map internal
+        Ok(vec![MemoryRange {
+            start: 0,
+            end: self.get_max_physical_addr()?,
+        }])
+    }
+
     fn get_driver_type(&self) -> DriverType {
         DriverType::Memflow
     }
@@ -0,0 +1,423 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::error::Error;
+
+use crate::api::events::{Event, EventReplyType, EventType, InterceptType};
+use crate::api::params::DriverInitParams;
+use crate::api::registers::{DebugRegisters, ExtendedRegisters, Registers, X86Registers};
+use crate::api::{
+    Access, Capabilities, DriverType, Intercepts, Introspectable, Operations, PAGE_SHIFT,
+};
+use crate::errors::MicrovmiError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MockDriverError {
+    #[error("Mock driver requires a mock parameter")]
+    MissingMockParameter,
+    #[error("physical address {paddr:#X} (size {size:#X}) is out of bounds ({max_addr:#X})")]
+    OutOfBounds {
+        paddr: u64,
+        size: u64,
+        max_addr: u64,
+    },
+    #[error("invalid vcpu {0}")]
+    InvalidVcpu(u16),
+}
+
+impl From<MockDriverError> for MicrovmiError {
+    fn from(error: MockDriverError) -> Self {
+        match error {
+            MockDriverError::OutOfBounds { paddr, .. } => MicrovmiError::InvalidAddress {
+                address: paddr,
+                source: Box::new(error),
+            },
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+/// In-memory driver, for testing VMI logic without a hypervisor
+///
+/// The guest physical memory is a byte vector, and the events returned by `listen`
+/// are scripted with [`push_event`](#method.push_event).
+/// Replies sent with `reply_event` are recorded and can be inspected with [`replies`](#method.replies).
+pub struct Mock {
+    // refcells required because write methods take &self
+    memory: RefCell<Vec<u8>>,
+    vcpus: RefCell<Vec<Registers>>,
+    debug_registers: RefCell<Vec<DebugRegisters>>,
+    // (VCPU, index) -> MSR value, 0 by default
+    msrs: RefCell<HashMap<(u16, u32), u64>>,
+    // GFN -> access, pages are RWX by default
+    page_access: RefCell<HashMap<u64, Access>>,
+    events: VecDeque<Event>,
+    replies: Vec<(Event, EventReplyType)>,
+    paused: bool,
+}
+
+impl Mock {
+    pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
+        let mock_params = init_params
+            .mock
+            .ok_or(MockDriverError::MissingMockParameter)?;
+        debug!(
+            "init with {:#X} bytes of memory and {} vcpus",
+            mock_params.memory_size, mock_params.vcpu_count
+        );
+        Ok(Mock::from_memory(
+            vec![0; mock_params.memory_size.try_into()?],
+            mock_params.vcpu_count,
+        ))
+    }
+
+    /// Create a mock driver from the given guest physical memory content
+    pub fn from_memory(memory: Vec<u8>, vcpu_count: u16) -> Self {
+        Mock {
+            memory: RefCell::new(memory),
+            vcpus: RefCell::new(vec![
+                Registers::X86(X86Registers::default());
+                vcpu_count as usize
+            ]),
+            debug_registers: RefCell::new(vec![DebugRegisters::default(); vcpu_count as usize]),
+            msrs: RefCell::new(HashMap::new()),
+            page_access: RefCell::new(HashMap::new()),
+            events: VecDeque::new(),
+            replies: Vec::new(),
+            paused: false,
+        }
+    }
+
+    /// Queue an event, to be returned by a later call to `listen`
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    /// Number of queued events which have not been returned by `listen` yet
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    /// The events acknowledged with `reply_event`, in order
+    pub fn replies(&self) -> &[(Event, EventReplyType)] {
+        &self.replies
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn check_bounds(&self, paddr: u64, size: usize) -> Result<(), MockDriverError> {
+        let max_addr = self.memory.borrow().len() as u64;
+        let size = size as u64;
+        if paddr.checked_add(size).map_or(true, |end| end > max_addr) {
+            return Err(MockDriverError::OutOfBounds {
+                paddr,
+                size,
+                max_addr,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_vcpu(&self, vcpu: u16) -> Result<usize, MockDriverError> {
+        let index = vcpu as usize;
+        if index >= self.vcpus.borrow().len() {
+            return Err(MockDriverError::InvalidVcpu(vcpu));
+        }
+        Ok(index)
+    }
+}
+
+impl Introspectable for Mock {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::all(),
+            intercepts: Intercepts::all(),
+        }
+    }
+
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
+        Ok(self.vcpus.borrow().len().try_into()?)
+    }
+
+    fn read_physical(
+        &self,
+        paddr: u64,
+        buf: &mut [u8],
+        bytes_read: &mut u64,
+    ) -> Result<(), MicrovmiError> {
+        *bytes_read = 0;
+        self.check_bounds(paddr, buf.len())?;
+        let start = paddr as usize;
+        buf.copy_from_slice(&self.memory.borrow()[start..start + buf.len()]);
+        *bytes_read = buf.len() as u64;
+        Ok(())
+    }
+
+    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
+        self.check_bounds(paddr, buf.len())?;
+        let start = paddr as usize;
+        self.memory.borrow_mut()[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Ok(self.memory.borrow().len() as u64)
+    }
+
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
+        let index = self.check_vcpu(vcpu)?;
+        Ok(self.vcpus.borrow()[index].clone())
+    }
+
+    fn write_registers(&self, vcpu: u16, reg: Registers) -> Result<(), MicrovmiError> {
+        let index = self.check_vcpu(vcpu)?;
+        self.vcpus.borrow_mut()[index] = reg;
+        Ok(())
+    }
+
+    fn read_extended_registers(&self, vcpu: u16) -> Result<ExtendedRegisters, MicrovmiError> {
+        self.check_vcpu(vcpu)?;
+        Ok(ExtendedRegisters::default())
+    }
+
+    fn read_debug_registers(&self, vcpu: u16) -> Result<DebugRegisters, MicrovmiError> {
+        let index = self.check_vcpu(vcpu)?;
+        Ok(self.debug_registers.borrow()[index])
+    }
+
+    fn write_debug_registers(&self, vcpu: u16, regs: DebugRegisters) -> Result<(), MicrovmiError> {
+        let index = self.check_vcpu(vcpu)?;
+        self.debug_registers.borrow_mut()[index] = regs;
+        Ok(())
+    }
+
+    fn read_msr(&self, vcpu: u16, index: u32) -> Result<u64, MicrovmiError> {
+        self.check_vcpu(vcpu)?;
+        Ok(*self.msrs.borrow().get(&(vcpu, index)).unwrap_or(&0))
+    }
+
+    fn write_msr(&self, vcpu: u16, index: u32, value: u64) -> Result<(), MicrovmiError> {
+        self.check_vcpu(vcpu)?;
+        self.msrs.borrow_mut().insert((vcpu, index), value);
+        Ok(())
+    }
+
+    fn get_page_access(&self, paddr: u64) -> Result<Access, MicrovmiError> {
+        self.check_bounds(paddr, 1)?;
+        Ok(*self
+            .page_access
+            .borrow()
+            .get(&(paddr >> PAGE_SHIFT))
+            .unwrap_or(&Access::RWX))
+    }
+
+    fn set_page_access(&self, paddr: u64, access: Access) -> Result<(), MicrovmiError> {
+        self.check_bounds(paddr, 1)?;
+        self.page_access
+            .borrow_mut()
+            .insert(paddr >> PAGE_SHIFT, access);
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        self.paused = true;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        self.paused = false;
+        Ok(())
+    }
+
+    fn toggle_intercept(
+        &mut self,
+        vcpu: u16,
+        _intercept_type: InterceptType,
+        _enabled: bool,
+    ) -> Result<(), MicrovmiError> {
+        // events are scripted: intercepts only need to target a valid vcpu
+        self.check_vcpu(vcpu)?;
+        Ok(())
+    }
+
+    fn listen(&mut self, _timeout: u32) -> Result<Option<Event>, MicrovmiError> {
+        // an empty queue behaves like a timeout
+        Ok(self.events.pop_front())
+    }
+
+    fn reply_event(
+        &mut self,
+        event: Event,
+        reply_type: EventReplyType,
+    ) -> Result<(), MicrovmiError> {
+        match &reply_type {
+            EventReplyType::SetRegisters(regs) => self.write_registers(event.vcpu, regs.clone())?,
+            EventReplyType::SkipInstruction => {
+                let insn_len = match event.kind {
+                    EventType::Breakpoint { insn_len, .. } => insn_len,
+                    _ => return Err(MicrovmiError::Unsupported("SkipInstruction reply")),
+                };
+                let Registers::X86(mut regs) = self.read_registers(event.vcpu)?;
+                regs.rip += u64::from(insn_len);
+                self.write_registers(event.vcpu, Registers::X86(regs))?;
+            }
+            // no guest execution to act upon
+            _ => (),
+        }
+        self.replies.push((event, reply_type));
+        Ok(())
+    }
+
+    fn get_driver_type(&self) -> DriverType {
+        DriverType::Mock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::events::CrType;
+    use crate::api::params::MockInitParams;
+
+    use super::*;
+
+    #[test]
+    fn test_fail_to_create_mock_driver_without_mock_parameter() {
+        assert!(Mock::new(DriverInitParams::default()).is_err());
+    }
+
+    #[test]
+    fn test_memory_and_registers() {
+        let drv = Mock::new(DriverInitParams {
+            mock: Some(MockInitParams {
+                memory_size: 0x2000,
+                vcpu_count: 2,
+            }),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(2, drv.get_vcpu_count().unwrap());
+        assert_eq!(0x2000, drv.get_max_physical_addr().unwrap());
+
+        drv.write_physical(0x1FFE, &[0xAA, 0xBB]).unwrap();
+        let mut buf = [0u8; 2];
+        let mut bytes_read = 0;
+        drv.read_physical(0x1FFE, &mut buf, &mut bytes_read)
+            .unwrap();
+        assert_eq!([0xAA, 0xBB], buf);
+        assert!(matches!(
+            drv.read_physical(0x1FFF, &mut buf, &mut bytes_read),
+            Err(MicrovmiError::InvalidAddress {
+                address: 0x1FFF,
+                ..
+            })
+        ));
+
+        drv.write_registers(
+            1,
+            Registers::X86(X86Registers {
+                rip: 0x1000,
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        let Registers::X86(regs) = drv.read_registers(1).unwrap();
+        assert_eq!(0x1000, regs.rip);
+        assert!(drv.read_registers(2).is_err());
+        drv.write_debug_registers(
+            1,
+            DebugRegisters {
+                dr7: 0x1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(0x1, drv.read_debug_registers(1).unwrap().dr7);
+        assert_eq!(0, drv.read_debug_registers(0).unwrap().dr7);
+
+        drv.write_msr(1, 0xC000_0102, 0xFFFF_F800_0000_0000)
+            .unwrap();
+        assert_eq!(0xFFFF_F800_0000_0000, drv.read_msr(1, 0xC000_0102).unwrap());
+        assert_eq!(0, drv.read_msr(0, 0xC000_0102).unwrap());
+        assert_eq!(0, drv.read_extended_registers(1).unwrap().mxcsr);
+        assert!(drv.write_msr(2, 0xC000_0102, 0).is_err());
+
+        assert_eq!(Access::RWX, drv.get_page_access(0x1000).unwrap());
+        drv.set_page_access(0x1000, Access::R).unwrap();
+        assert_eq!(Access::R, drv.get_page_access(0x1FFF).unwrap());
+    }
+
+    #[test]
+    fn test_scripted_events() {
+        let mut drv = Mock::from_memory(vec![0; 0x1000], 1);
+        drv.push_event(Event {
+            vcpu: 0,
+            kind: EventType::Breakpoint {
+                gpa: 0x100,
+                insn_len: 1,
+            },
+            regs: Some(Registers::X86(X86Registers {
+                rip: 0x100,
+                ..Default::default()
+            })),
+        });
+        assert_eq!(1, drv.pending_events());
+
+        let event = drv.listen(0).unwrap().unwrap();
+        assert!(matches!(
+            event.kind,
+            EventType::Breakpoint { gpa: 0x100, .. }
+        ));
+        assert!(matches!(
+            event.regs,
+            Some(Registers::X86(X86Registers { rip: 0x100, .. }))
+        ));
+        drv.reply_event(event, EventReplyType::Continue).unwrap();
+        assert!(drv.listen(0).unwrap().is_none());
+        assert_eq!(1, drv.replies().len());
+        assert_eq!(0, drv.replies()[0].0.vcpu);
+    }
+
+    #[test]
+    fn test_reply_modifies_registers() {
+        let mut drv = Mock::from_memory(vec![0; 0x1000], 1);
+        let breakpoint = || Event {
+            vcpu: 0,
+            kind: EventType::Breakpoint {
+                gpa: 0x100,
+                insn_len: 1,
+            },
+            regs: None,
+        };
+        drv.reply_event(
+            breakpoint(),
+            EventReplyType::SetRegisters(Registers::X86(X86Registers {
+                rip: 0x100,
+                ..Default::default()
+            })),
+        )
+        .unwrap();
+        drv.reply_event(breakpoint(), EventReplyType::SkipInstruction)
+            .unwrap();
+        let Registers::X86(regs) = drv.read_registers(0).unwrap();
+        assert_eq!(0x101, regs.rip);
+
+        let cr = Event {
+            vcpu: 0,
+            kind: EventType::Cr {
+                cr_type: CrType::Cr3,
+                new: 0,
+                old: 0,
+            },
+            regs: None,
+        };
+        assert!(matches!(
+            drv.reply_event(cr, EventReplyType::SkipInstruction),
+            Err(MicrovmiError::Unsupported(_))
+        ));
+        assert_eq!(2, drv.replies().len());
+    }
+}
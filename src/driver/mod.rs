@@ -1,8 +1,50 @@
+#[cfg(feature = "crashdump")]
+pub mod crashdump;
+#[cfg(feature = "elfcore")]
+pub mod elfcore;
+#[cfg(feature = "file")]
+pub mod file;
 #[cfg(feature = "kvm")]
 pub mod kvm;
+#[cfg(feature = "lime")]
+pub mod lime;
 #[cfg(feature = "mflow")]
 pub mod memflow;
+#[cfg(feature = "mock")]
+pub mod mock;
 #[cfg(feature = "virtualbox")]
 pub mod virtualbox;
 #[cfg(feature = "xen")]
 pub mod xen;
+
+/// Unique path in the temporary directory, keeping the extension of `name`
+///
+/// Tests run in parallel, and may run several times concurrently.
+#[cfg(all(
+    test,
+    any(
+        feature = "file",
+        feature = "elfcore",
+        feature = "lime",
+        feature = "crashdump"
+    )
+))]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap().to_str().unwrap();
+    let unique = format!(
+        "{}_{}_{}",
+        stem,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    );
+    let path = std::env::temp_dir().join(unique);
+    match name.extension() {
+        Some(extension) => path.with_extension(extension),
+        None => path,
+    }
+}
@@ -4,7 +4,11 @@ use fdp::{RegisterType, FDP};
 
 use crate::api::params::DriverInitParams;
 use crate::api::registers::{Registers, SegmentReg, SystemTableReg, X86Registers};
-use crate::api::{DriverType, Introspectable, PAGE_SIZE};
+use crate::api::{Capabilities, DriverType, Introspectable, Operations, PAGE_SIZE};
+use crate::errors::MicrovmiError;
+
+const EFER_LME: u64 = 1 << 8;
+const EFER_LMA: u64 = 1 << 10;
 
 #[derive(Debug)]
 pub struct VBox {
@@ -25,13 +29,27 @@ impl VBox {
             .vm_name;
 
         // init FDP
-        let fdp = FDP::new(&domain_name)?;
+        let fdp = FDP::new(&domain_name).map_err(|source| MicrovmiError::VmNotFound {
+            source: source.into(),
+        })?;
         Ok(VBox { fdp })
     }
 }
 
 impl Introspectable for VBox {
-    fn get_vcpu_count(&self) -> Result<u16, Box<dyn Error>> {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::VCPU_COUNT
+                | Operations::READ_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::READ_REGISTERS
+                | Operations::PAUSE
+                | Operations::RESUME,
+            ..Default::default()
+        }
+    }
+
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
         // no API to fetch VCPU count, hardcode to 1 for now
         Ok(1)
     }
@@ -41,7 +59,7 @@ impl Introspectable for VBox {
         paddr: u64,
         buf: &mut [u8],
         bytes_read: &mut u64,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         for (i, chunk) in buf.chunks_mut(PAGE_SIZE as usize).enumerate() {
             let offset = i * PAGE_SIZE as usize;
             let cur_paddr = paddr + offset as u64;
@@ -51,13 +69,13 @@ impl Introspectable for VBox {
         Ok(())
     }
 
-    fn get_max_physical_addr(&self) -> Result<u64, Box<dyn Error>> {
-        self.fdp.get_physical_memory_size()
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
+        Ok(self.fdp.get_physical_memory_size()?)
     }
 
-    fn read_registers(&self, vcpu: u16) -> Result<Registers, Box<dyn Error>> {
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
         let fdp_vcpu = vcpu as u32;
-        let regs = X86Registers {
+        let mut regs = X86Registers {
             rax: self.fdp.read_register(fdp_vcpu, RegisterType::RAX)?,
             rbx: self.fdp.read_register(fdp_vcpu, RegisterType::RBX)?,
             rcx: self.fdp.read_register(fdp_vcpu, RegisterType::RCX)?,
@@ -113,15 +131,19 @@ impl Introspectable for VBox {
             },
             ..Default::default()
         };
+        // FDP doesn't expose EFER, but only a long mode kernel can place its IDT above 4G
+        if regs.idt.base > u64::from(u32::MAX) {
+            regs.msr_efer = EFER_LMA | EFER_LME;
+        }
         Ok(Registers::X86(regs))
     }
 
-    fn pause(&mut self) -> Result<(), Box<dyn Error>> {
-        self.fdp.pause()
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
+        Ok(self.fdp.pause()?)
     }
 
-    fn resume(&mut self) -> Result<(), Box<dyn Error>> {
-        self.fdp.resume()
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
+        Ok(self.fdp.resume()?)
     }
 
     fn get_driver_type(&self) -> DriverType {
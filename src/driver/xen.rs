@@ -1,10 +1,14 @@
+use std::alloc::{dealloc, Layout};
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::error::Error;
 use std::io::Error as IoError;
 use std::io::ErrorKind;
 use std::mem;
 use std::num::TryFromIntError;
+use std::slice;
 
 use libc::{PROT_READ, PROT_WRITE};
 use nix::poll::PollFlags;
@@ -12,18 +16,27 @@ use nix::poll::{poll, PollFd};
 use xenctrl::consts::{PAGE_SHIFT, PAGE_SIZE};
 use xenctrl::error::XcError;
 use xenctrl::RING_HAS_UNCONSUMED_REQUESTS;
-use xenctrl::{XenControl, XenCr, XenEventType};
+use xenctrl::{
+    XenControl, XenCr, XenEventType, XenPageAccess, XEN_DOMCTL_DEBUG_OP_SINGLE_STEP_OFF,
+    XEN_DOMCTL_DEBUG_OP_SINGLE_STEP_ON,
+};
 use xenevtchn::XenEventChannel;
 use xenforeignmemory::{XenForeignMem, XenForeignMemoryError};
 use xenstore_rs::{XBTransaction, Xs, XsOpenFlags};
 use xenvmevent_sys::{
-    vm_event_back_ring, vm_event_response_t, VM_EVENT_FLAG_VCPU_PAUSED, VM_EVENT_INTERFACE_VERSION,
+    vm_event_back_ring, vm_event_regs_x86, vm_event_response_t, VM_EVENT_FLAG_EMULATE,
+    VM_EVENT_FLAG_SET_REGISTERS, VM_EVENT_FLAG_TOGGLE_SINGLESTEP, VM_EVENT_FLAG_VCPU_PAUSED,
+    VM_EVENT_INTERFACE_VERSION, VM_EVENT_REASON_MEM_ACCESS, VM_EVENT_REASON_MOV_TO_MSR,
+    VM_EVENT_REASON_SINGLESTEP, VM_EVENT_REASON_SOFTWARE_BREAKPOINT, VM_EVENT_REASON_WRITE_CTRLREG,
 };
 
-use crate::api::events::{CrType, Event, EventType, InterceptType};
+use crate::api::events::{CrType, Event, EventReplyType, EventType, InterceptType};
 use crate::api::params::DriverInitParams;
-use crate::api::registers::{Registers, SegmentReg, SystemTableReg, X86Registers};
-use crate::api::{DriverType, Introspectable};
+use crate::api::registers::{
+    DebugRegisters, ExtendedRegisters, Registers, SegmentReg, SystemTableReg, X86Registers,
+};
+use crate::api::{Access, Capabilities, DriverType, Intercepts, Introspectable, Operations};
+use crate::errors::MicrovmiError;
 
 #[derive(Debug)]
 pub struct Xen {
@@ -33,6 +46,19 @@ pub struct Xen {
     dom_name: String,
     domid: u32,
     back_ring: vm_event_back_ring,
+    // VCPU -> request waiting for a response
+    pending_requests: HashMap<u16, PendingRequest>,
+    // VCPUs being singlestepped
+    singlestep_vcpus: HashSet<u16>,
+}
+
+/// The part of a vm_event request required to build its response
+#[derive(Debug, Copy, Clone)]
+struct PendingRequest {
+    reason: u32,
+    vcpu_id: u32,
+    flags: u32,
+    regs: vm_event_regs_x86,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -45,6 +71,10 @@ pub enum XenDriverError {
     XenstoreDomainNotFoundError(String),
     #[error("event version mismatch: {0} <-> {1}")]
     EventVersionMismatch(u32, u32),
+    #[error("no pending event on vcpu {0}")]
+    NoPendingEvent(u16),
+    #[error("MSR {0:#X} is not available in the HVM context")]
+    UnsupportedMsr(u32),
     #[error("failed to convert integer")]
     TryFromIntError(#[from] TryFromIntError),
     #[error("failed to convert integer")]
@@ -59,6 +89,173 @@ pub enum XenDriverError {
     ForeignMemoryError(#[from] XenForeignMemoryError),
 }
 
+impl From<XenDriverError> for MicrovmiError {
+    fn from(error: XenDriverError) -> Self {
+        match error {
+            XenDriverError::XenstoreDomainNotFoundError(_) => MicrovmiError::VmNotFound {
+                source: Box::new(error),
+            },
+            XenDriverError::IoError(io_error) => MicrovmiError::from(io_error),
+            _ => MicrovmiError::Other {
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+impl From<&vm_event_regs_x86> for X86Registers {
+    fn from(regs: &vm_event_regs_x86) -> Self {
+        // segment selectors and descriptor tables are not part of the vm_event registers
+        X86Registers {
+            rax: regs.rax,
+            rbx: regs.rbx,
+            rcx: regs.rcx,
+            rdx: regs.rdx,
+            rsi: regs.rsi,
+            rdi: regs.rdi,
+            rsp: regs.rsp,
+            rbp: regs.rbp,
+            r8: regs.r8,
+            r9: regs.r9,
+            r10: regs.r10,
+            r11: regs.r11,
+            r12: regs.r12,
+            r13: regs.r13,
+            r14: regs.r14,
+            r15: regs.r15,
+            rip: regs.rip,
+            rflags: regs.rflags,
+            cr0: regs.cr0,
+            cr2: regs.cr2,
+            cr3: regs.cr3,
+            cr4: regs.cr4,
+            sysenter_cs: regs.sysenter_cs,
+            sysenter_esp: regs.sysenter_esp,
+            sysenter_eip: regs.sysenter_eip,
+            msr_efer: regs.msr_efer,
+            msr_star: regs.msr_star,
+            msr_lstar: regs.msr_lstar,
+            fs: SegmentReg {
+                base: regs.fs_base,
+                ..Default::default()
+            },
+            gs: SegmentReg {
+                base: regs.gs_base,
+                ..Default::default()
+            },
+            gdt: SystemTableReg {
+                base: regs.gdtr_base,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<Access> for XenPageAccess {
+    type Error = &'static str;
+    fn try_from(access: Access) -> Result<Self, Self::Error> {
+        match access {
+            Access::NIL => Ok(XenPageAccess::NIL),
+            Access::R => Ok(XenPageAccess::R),
+            Access::W => Ok(XenPageAccess::W),
+            Access::RW => Ok(XenPageAccess::RW),
+            Access::X => Ok(XenPageAccess::X),
+            Access::RX => Ok(XenPageAccess::RX),
+            Access::WX => Ok(XenPageAccess::WX),
+            Access::RWX => Ok(XenPageAccess::RWX),
+            _ => Err("invalid access value"),
+        }
+    }
+}
+
+impl From<XenPageAccess> for Access {
+    fn from(access: XenPageAccess) -> Self {
+        match access {
+            XenPageAccess::NIL => Access::NIL,
+            XenPageAccess::R => Access::R,
+            XenPageAccess::W => Access::W,
+            XenPageAccess::RW => Access::RW,
+            XenPageAccess::X => Access::X,
+            XenPageAccess::RX => Access::RX,
+            XenPageAccess::WX => Access::WX,
+            XenPageAccess::RWX => Access::RWX,
+        }
+    }
+}
+
+// MSRs saved in the HVM context
+const MSR_IA32_SYSENTER_CS: u32 = 0x174;
+const MSR_IA32_SYSENTER_ESP: u32 = 0x175;
+const MSR_IA32_SYSENTER_EIP: u32 = 0x176;
+const MSR_EFER: u32 = 0xC000_0080;
+const MSR_STAR: u32 = 0xC000_0081;
+const MSR_LSTAR: u32 = 0xC000_0082;
+const MSR_CSTAR: u32 = 0xC000_0083;
+const MSR_SYSCALL_MASK: u32 = 0xC000_0084;
+const MSR_SHADOW_GS_BASE: u32 = 0xC000_0102;
+const MSR_TSC_AUX: u32 = 0xC000_0103;
+
+/// HVM_SAVE_CODE(CPU_XSAVE)
+const HVM_SAVE_CODE_CPU_XSAVE: u16 = 16;
+// offsets in the hvm_hw_cpu_xsave record
+const CPU_XSAVE_XCR0: usize = 8;
+const CPU_XSAVE_SAVE_AREA: usize = 24;
+
+/// Find the record of the given type and instance in an HVM context
+///
+/// The context is a sequence of records, each one preceded by a hvm_save_descriptor
+/// (typecode: u16, instance: u16, length: u32).
+fn find_hvm_record(context: &[u8], typecode: u16, instance: u16) -> Option<&[u8]> {
+    let mut offset = 0;
+    while offset + 8 <= context.len() {
+        let record_type = u16::from_le_bytes([context[offset], context[offset + 1]]);
+        let record_instance = u16::from_le_bytes([context[offset + 2], context[offset + 3]]);
+        let mut length = [0; 4];
+        length.copy_from_slice(&context[offset + 4..offset + 8]);
+        let start = offset + 8;
+        let end = start.checked_add(u32::from_le_bytes(length) as usize)?;
+        if end > context.len() {
+            return None;
+        }
+        if record_type == typecode && record_instance == instance {
+            return Some(&context[start..end]);
+        }
+        offset = end;
+    }
+    None
+}
+
+/// Request reasons decoded by xenctrl get_event_type, which panics on the others
+const DECODED_EVENT_REASONS: [u32; 5] = [
+    VM_EVENT_REASON_WRITE_CTRLREG,
+    VM_EVENT_REASON_MOV_TO_MSR,
+    VM_EVENT_REASON_SOFTWARE_BREAKPOINT,
+    VM_EVENT_REASON_MEM_ACCESS,
+    VM_EVENT_REASON_SINGLESTEP,
+];
+/// Update the registers which are set by a VM_EVENT_FLAG_SET_REGISTERS response
+fn set_vm_event_regs(regs: &mut vm_event_regs_x86, x86_registers: &X86Registers) {
+    regs.rax = x86_registers.rax;
+    regs.rbx = x86_registers.rbx;
+    regs.rcx = x86_registers.rcx;
+    regs.rdx = x86_registers.rdx;
+    regs.rsi = x86_registers.rsi;
+    regs.rdi = x86_registers.rdi;
+    regs.rsp = x86_registers.rsp;
+    regs.rbp = x86_registers.rbp;
+    regs.r8 = x86_registers.r8;
+    regs.r9 = x86_registers.r9;
+    regs.r10 = x86_registers.r10;
+    regs.r11 = x86_registers.r11;
+    regs.r12 = x86_registers.r12;
+    regs.r13 = x86_registers.r13;
+    regs.r14 = x86_registers.r14;
+    regs.r15 = x86_registers.r15;
+    regs.rip = x86_registers.rip;
+    regs.rflags = x86_registers.rflags;
+}
+
 impl Xen {
     pub fn new(init_params: DriverInitParams) -> Result<Self, Box<dyn Error>> {
         let domain_name = init_params
@@ -96,9 +293,10 @@ impl Xen {
             }
         }
         if !found {
-            return Err(Box::new(XenDriverError::XenstoreDomainNotFoundError(
-                domain_name,
-            )));
+            return Err(
+                MicrovmiError::from(XenDriverError::XenstoreDomainNotFoundError(domain_name))
+                    .into(),
+            );
         }
 
         let mut xc = XenControl::new(None, None, 0)?;
@@ -113,19 +311,68 @@ impl Xen {
             dom_name: domain_name,
             domid: cand_domid,
             back_ring,
+            pending_requests: HashMap::new(),
+            singlestep_vcpus: HashSet::new(),
         };
         trace!("Initialized {:#?}", xen);
         Ok(xen)
     }
+
+    /// Answer a request, resuming its vcpu
+    fn put_response(
+        &mut self,
+        req: &PendingRequest,
+        flags: u32,
+        regs: Option<vm_event_regs_x86>,
+    ) -> Result<(), XenDriverError> {
+        let mut rsp = unsafe { mem::MaybeUninit::<vm_event_response_t>::zeroed().assume_init() };
+        rsp.reason = req.reason;
+        rsp.version = VM_EVENT_INTERFACE_VERSION;
+        rsp.vcpu_id = req.vcpu_id;
+        rsp.flags = (req.flags & VM_EVENT_FLAG_VCPU_PAUSED) | flags;
+        if let Some(regs) = regs {
+            rsp.flags |= VM_EVENT_FLAG_SET_REGISTERS;
+            rsp.data.regs.x86 = regs;
+        }
+        self.xc.put_response(&mut rsp, &mut self.back_ring)?;
+        self.xev.xenevtchn_notify()?;
+        Ok(())
+    }
 }
 
 impl Introspectable for Xen {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            operations: Operations::VCPU_COUNT
+                | Operations::READ_PHYSICAL
+                | Operations::WRITE_PHYSICAL
+                | Operations::MAX_PHYSICAL_ADDR
+                | Operations::READ_REGISTERS
+                | Operations::WRITE_REGISTERS
+                | Operations::READ_DEBUG_REGISTERS
+                | Operations::WRITE_DEBUG_REGISTERS
+                | Operations::READ_MSR
+                | Operations::WRITE_MSR
+                | Operations::READ_EXTENDED_REGISTERS
+                | Operations::GET_PAGE_ACCESS
+                | Operations::SET_PAGE_ACCESS
+                | Operations::PAUSE
+                | Operations::RESUME
+                | Operations::EVENTS,
+            intercepts: Intercepts::CR
+                | Intercepts::MSR
+                | Intercepts::BREAKPOINT
+                | Intercepts::PAGEFAULT
+                | Intercepts::SINGLESTEP,
+        }
+    }
+
     fn read_physical(
         &self,
         paddr: u64,
         buf: &mut [u8],
         bytes_read: &mut u64,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         let mut cur_paddr: u64;
         let mut count_mut: u64 = buf.len() as u64;
         let mut buf_offset: u64 = 0;
@@ -137,10 +384,12 @@ impl Introspectable for Xen {
             let gfn = cur_paddr >> PAGE_SHIFT;
             let page_offset = u64::from(PAGE_SIZE - 1) & cur_paddr;
             // map gfn
-            let page = self
-                .xen_fgn
-                .map(self.domid, PROT_READ, gfn)
-                .map_err(XenDriverError::from)?;
+            let page = self.xen_fgn.map(self.domid, PROT_READ, gfn).map_err(|e| {
+                MicrovmiError::InvalidAddress {
+                    address: cur_paddr,
+                    source: Box::new(XenDriverError::from(e)),
+                }
+            })?;
             // determine how much we can read
             let read_len = if (page_offset + count_mut as u64) > u64::from(PAGE_SIZE) {
                 u64::from(PAGE_SIZE) - page_offset
@@ -165,7 +414,7 @@ impl Introspectable for Xen {
         Ok(())
     }
 
-    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), Box<dyn Error>> {
+    fn write_physical(&self, paddr: u64, buf: &[u8]) -> Result<(), MicrovmiError> {
         let mut phys_address: u64;
         let mut offset: u64;
         let mut count_mut: u64 = buf.len() as u64;
@@ -177,10 +426,12 @@ impl Introspectable for Xen {
             let pfn = phys_address >> PAGE_SHIFT;
             offset = u64::from(PAGE_SIZE - 1) & phys_address;
             // map pfn
-            let page = self
-                .xen_fgn
-                .map(self.domid, PROT_WRITE, pfn)
-                .map_err(XenDriverError::from)?;
+            let page = self.xen_fgn.map(self.domid, PROT_WRITE, pfn).map_err(|e| {
+                MicrovmiError::InvalidAddress {
+                    address: phys_address,
+                    source: Box::new(XenDriverError::from(e)),
+                }
+            })?;
             // determine how much we can write
             let write_len = if (offset + count_mut as u64) > u64::from(PAGE_SIZE) {
                 u64::from(PAGE_SIZE) - offset
@@ -199,7 +450,7 @@ impl Introspectable for Xen {
         Ok(())
     }
 
-    fn get_max_physical_addr(&self) -> Result<u64, Box<dyn Error>> {
+    fn get_max_physical_addr(&self) -> Result<u64, MicrovmiError> {
         let max_gpfn = self
             .xc
             .domain_maximum_gpfn(self.domid)
@@ -207,7 +458,7 @@ impl Introspectable for Xen {
         Ok(max_gpfn << PAGE_SHIFT)
     }
 
-    fn get_vcpu_count(&self) -> Result<u16, Box<dyn Error>> {
+    fn get_vcpu_count(&self) -> Result<u16, MicrovmiError> {
         let domain_info = self
             .xc
             .domain_getinfo(self.domid)
@@ -217,7 +468,7 @@ impl Introspectable for Xen {
             .map_err(XenDriverError::from)?)
     }
 
-    fn read_registers(&self, vcpu: u16) -> Result<Registers, Box<dyn Error>> {
+    fn read_registers(&self, vcpu: u16) -> Result<Registers, MicrovmiError> {
         let hvm_cpu = self
             .xc
             .domain_hvm_getcontext_partial(self.domid, vcpu)
@@ -299,8 +550,11 @@ impl Introspectable for Xen {
         }))
     }
 
-    fn write_registers(&self, vcpu: u16, reg: Registers) -> Result<(), Box<dyn Error>> {
-        let (buffer, mut cpu, size) = self.xc.domain_hvm_getcontext(self.domid, vcpu)?;
+    fn write_registers(&self, vcpu: u16, reg: Registers) -> Result<(), MicrovmiError> {
+        let (buffer, mut cpu, size) = self
+            .xc
+            .domain_hvm_getcontext(self.domid, vcpu)
+            .map_err(MicrovmiError::other)?;
         match reg {
             Registers::X86(x86_registers) => {
                 cpu.rax = x86_registers.rax;
@@ -382,21 +636,91 @@ impl Introspectable for Xen {
                     .map_err(XenDriverError::from)?;
             }
         }
-        self.xc.domain_hvm_setcontext(
-            self.domid,
-            buffer,
-            size.try_into().map_err(XenDriverError::from)?,
-        )?;
+        self.xc
+            .domain_hvm_setcontext(
+                self.domid,
+                buffer,
+                size.try_into().map_err(XenDriverError::from)?,
+            )
+            .map_err(MicrovmiError::other)?;
         Ok(())
     }
 
-    fn listen(&mut self, timeout: u32) -> Result<Option<Event>, Box<dyn Error>> {
+    fn read_extended_registers(&self, vcpu: u16) -> Result<ExtendedRegisters, MicrovmiError> {
+        let (buffer, hvm_cpu, size) = self
+            .xc
+            .domain_hvm_getcontext(self.domid, vcpu)
+            .map_err(MicrovmiError::other)?;
+        let size: usize = size.try_into().map_err(XenDriverError::from)?;
+        let context = unsafe { slice::from_raw_parts(buffer as *const u8, size) };
+        let regs = match find_hvm_record(context, HVM_SAVE_CODE_CPU_XSAVE, vcpu) {
+            Some(record) if record.len() >= CPU_XSAVE_SAVE_AREA => {
+                let mut xcr0 = [0; 8];
+                xcr0.copy_from_slice(&record[CPU_XSAVE_XCR0..CPU_XSAVE_XCR0 + 8]);
+                // the save area is in the standard format
+                ExtendedRegisters::from_xsave(
+                    &record[CPU_XSAVE_SAVE_AREA..],
+                    u64::from_le_bytes(xcr0),
+                )
+            }
+            // no XSAVE record if the vcpu doesn't use XSAVE, the CPU record holds the FXSAVE region
+            _ => ExtendedRegisters::from_fxsave(&hvm_cpu.fpu_regs),
+        };
+        // allocated by domain_hvm_getcontext
+        unsafe {
+            dealloc(
+                buffer as *mut u8,
+                Layout::from_size_align_unchecked(size, 1),
+            )
+        };
+        Ok(regs)
+    }
+
+    fn read_debug_registers(&self, vcpu: u16) -> Result<DebugRegisters, MicrovmiError> {
+        let hvm_cpu = self
+            .xc
+            .domain_hvm_getcontext_partial(self.domid, vcpu)
+            .map_err(XenDriverError::from)?;
+        Ok(DebugRegisters {
+            dr0: hvm_cpu.dr0,
+            dr1: hvm_cpu.dr1,
+            dr2: hvm_cpu.dr2,
+            dr3: hvm_cpu.dr3,
+            dr6: hvm_cpu.dr6,
+            dr7: hvm_cpu.dr7,
+        })
+    }
+
+    fn write_debug_registers(&self, vcpu: u16, regs: DebugRegisters) -> Result<(), MicrovmiError> {
+        let (buffer, mut cpu, size) = self
+            .xc
+            .domain_hvm_getcontext(self.domid, vcpu)
+            .map_err(MicrovmiError::other)?;
+        cpu.dr0 = regs.dr0;
+        cpu.dr1 = regs.dr1;
+        cpu.dr2 = regs.dr2;
+        cpu.dr3 = regs.dr3;
+        cpu.dr6 = regs.dr6;
+        cpu.dr7 = regs.dr7;
+        self.xc
+            .domain_hvm_setcontext(
+                self.domid,
+                buffer,
+                size.try_into().map_err(XenDriverError::from)?,
+            )
+            .map_err(MicrovmiError::other)?;
+        Ok(())
+    }
+
+    fn listen(&mut self, timeout: u32) -> Result<Option<Event>, MicrovmiError> {
         let fd = self.xev.xenevtchn_fd().map_err(XenDriverError::from)?;
         let fd_struct = PollFd::new(fd, PollFlags::POLLIN | PollFlags::POLLERR);
         let mut fds = [fd_struct];
         let mut vcpu: u16 = 0;
         let mut event_type = unsafe { mem::MaybeUninit::<EventType>::zeroed().assume_init() };
-        let poll_result = poll(&mut fds, timeout.try_into().map_err(XenDriverError::from)?)?;
+        let mut regs = None;
+        let poll_result = poll(&mut fds, timeout.try_into().map_err(XenDriverError::from)?)
+            .map_err(XenDriverError::from)?;
         let mut pending_event_port = -1;
         if poll_result == 1 {
             pending_event_port = self.xev.xenevtchn_pending().map_err(XenDriverError::from)?;
@@ -417,12 +741,29 @@ impl Introspectable for Xen {
             && RING_HAS_UNCONSUMED_REQUESTS!(back_ring_ptr) != 0
         {
             flag = true;
-            let req = self.xc.get_request(back_ring_ptr)?;
+            let req = self
+                .xc
+                .get_request(back_ring_ptr)
+                .map_err(MicrovmiError::other)?;
             if req.version != VM_EVENT_INTERFACE_VERSION {
-                return Err(Box::new(XenDriverError::EventVersionMismatch(
+                return Err(XenDriverError::EventVersionMismatch(
                     req.version,
                     VM_EVENT_INTERFACE_VERSION,
-                )));
+                )
+                .into());
+            }
+            // the request carries the vcpu registers at the time of the event
+            let req_regs = unsafe { req.data.regs.x86 };
+            let pending_request = PendingRequest {
+                reason: req.reason,
+                vcpu_id: req.vcpu_id,
+                flags: req.flags,
+                regs: req_regs,
+            };
+            if !DECODED_EVENT_REASONS.contains(&req.reason) {
+                // the vcpu would stay paused until the request is answered
+                self.put_response(&pending_request, 0, None)?;
+                return Err(MicrovmiError::Unsupported("vm_event request reason"));
             }
             let xen_event_type = (self.xc.get_event_type(req)).map_err(XenDriverError::from)?;
             event_type = match xen_event_type {
@@ -436,39 +777,88 @@ impl Introspectable for Xen {
                     old,
                 },
                 XenEventType::Msr { msr_type, value } => EventType::Msr { msr_type, value },
-                XenEventType::Breakpoint { insn_len, .. } => {
-                    EventType::Breakpoint { gpa: 0, insn_len }
+                XenEventType::Breakpoint { gfn, insn_len, .. } => EventType::Breakpoint {
+                    // the request only reports the frame, the offset comes from RIP
+                    gpa: (gfn << PAGE_SHIFT) | (u64::from(PAGE_SIZE - 1) & req_regs.rip),
+                    insn_len,
+                },
+                XenEventType::Singlestep { gfn } => EventType::Singlestep {
+                    gpa: (gfn << PAGE_SHIFT) | (u64::from(PAGE_SIZE - 1) & req_regs.rip),
+                },
+                XenEventType::Pagefault { gva, access, .. } => {
+                    // xenctrl leaves gpa to 0, the request holds the frame and offset
+                    let mem_access = unsafe { req.u.mem_access };
+                    EventType::Pagefault {
+                        gva,
+                        gpa: (mem_access.gfn << PAGE_SHIFT) | mem_access.offset,
+                        // access holds the MEM_ACCESS_* flags of the request, whose
+                        // R/W/X bits match Access
+                        access: Access::from_bits_truncate(access),
+                    }
                 }
-                _ => unimplemented!(),
             };
             vcpu = req.vcpu_id.try_into().map_err(XenDriverError::from)?;
-            let mut rsp =
-                unsafe { mem::MaybeUninit::<vm_event_response_t>::zeroed().assume_init() };
-            rsp.reason = req.reason;
-            rsp.version = VM_EVENT_INTERFACE_VERSION;
-            rsp.vcpu_id = req.vcpu_id;
-            rsp.flags = req.flags & VM_EVENT_FLAG_VCPU_PAUSED;
-            self.xc
-                .put_response(&mut rsp, &mut self.back_ring)
-                .map_err(XenDriverError::from)?;
+            regs = Some(Registers::X86(X86Registers::from(&req_regs)));
+            // the response is sent by reply_event
+            self.pending_requests.insert(vcpu, pending_request);
         }
         self.xev.xenevtchn_notify().map_err(XenDriverError::from)?;
         if flag {
             Ok(Some(Event {
                 vcpu,
                 kind: event_type,
+                regs,
             }))
         } else {
             Ok(None)
         }
     }
 
+    fn reply_event(
+        &mut self,
+        event: Event,
+        reply_type: EventReplyType,
+    ) -> Result<(), MicrovmiError> {
+        let req = self
+            .pending_requests
+            .remove(&event.vcpu)
+            .ok_or(XenDriverError::NoPendingEvent(event.vcpu))?;
+        let mut rsp_flags = 0;
+        // registers to be set by Xen before resuming the vcpu
+        let mut rsp_regs = None;
+        match reply_type {
+            // the vcpu resumes on the instruction which triggered the event
+            EventReplyType::Continue | EventReplyType::Retry => (),
+            EventReplyType::SetRegisters(Registers::X86(x86_registers)) => {
+                let mut regs = req.regs;
+                set_vm_event_regs(&mut regs, &x86_registers);
+                rsp_regs = Some(regs);
+            }
+            EventReplyType::SkipInstruction => {
+                let insn_len = match event.kind {
+                    EventType::Breakpoint { insn_len, .. } => insn_len,
+                    _ => {
+                        // the request must be answered anyway
+                        self.pending_requests.insert(event.vcpu, req);
+                        return Err(MicrovmiError::Unsupported("SkipInstruction reply"));
+                    }
+                };
+                let mut regs = req.regs;
+                regs.rip += u64::from(insn_len);
+                rsp_regs = Some(regs);
+            }
+            EventReplyType::Emulate => rsp_flags |= VM_EVENT_FLAG_EMULATE,
+            EventReplyType::SingleStep => rsp_flags |= VM_EVENT_FLAG_TOGGLE_SINGLESTEP,
+        }
+        Ok(self.put_response(&req, rsp_flags, rsp_regs)?)
+    }
+
     fn toggle_intercept(
         &mut self,
-        _vcpu: u16,
+        vcpu: u16,
         intercept_type: InterceptType,
         enabled: bool,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), MicrovmiError> {
         match intercept_type {
             InterceptType::Cr(micro_cr_type) => {
                 let xen_cr = match micro_cr_type {
@@ -489,11 +879,102 @@ impl Introspectable for Xen {
                 .xc
                 .monitor_software_breakpoint(self.domid, enabled)
                 .map_err(XenDriverError::from)?),
-            _ => unimplemented!(),
+            // there is no monitor op for memory access events: the monitor ring
+            // always reports them, for the pages restricted with set_page_access
+            InterceptType::Pagefault => Ok(()),
+            InterceptType::Singlestep => {
+                // the monitor is shared by the domain, keep it while a vcpu is stepping
+                if enabled && self.singlestep_vcpus.is_empty() {
+                    self.xc
+                        .monitor_singlestep(self.domid, true)
+                        .map_err(XenDriverError::from)?;
+                }
+                let op = if enabled {
+                    XEN_DOMCTL_DEBUG_OP_SINGLE_STEP_ON
+                } else {
+                    XEN_DOMCTL_DEBUG_OP_SINGLE_STEP_OFF
+                };
+                self.xc
+                    .domain_debug_control(self.domid, op, u32::from(vcpu))
+                    .map_err(XenDriverError::from)?;
+                if enabled {
+                    self.singlestep_vcpus.insert(vcpu);
+                } else if self.singlestep_vcpus.remove(&vcpu) && self.singlestep_vcpus.is_empty() {
+                    self.xc
+                        .monitor_singlestep(self.domid, false)
+                        .map_err(XenDriverError::from)?;
+                }
+                Ok(())
+            }
         }
     }
 
-    fn pause(&mut self) -> Result<(), Box<dyn Error>> {
+    fn read_msr(&self, vcpu: u16, index: u32) -> Result<u64, MicrovmiError> {
+        let hvm_cpu = self
+            .xc
+            .domain_hvm_getcontext_partial(self.domid, vcpu)
+            .map_err(XenDriverError::from)?;
+        let value = match index {
+            MSR_IA32_SYSENTER_CS => hvm_cpu.sysenter_cs,
+            MSR_IA32_SYSENTER_ESP => hvm_cpu.sysenter_esp,
+            MSR_IA32_SYSENTER_EIP => hvm_cpu.sysenter_eip,
+            MSR_EFER => hvm_cpu.msr_efer,
+            MSR_STAR => hvm_cpu.msr_star,
+            MSR_LSTAR => hvm_cpu.msr_lstar,
+            MSR_CSTAR => hvm_cpu.msr_cstar,
+            MSR_SYSCALL_MASK => hvm_cpu.msr_syscall_mask,
+            MSR_SHADOW_GS_BASE => hvm_cpu.shadow_gs,
+            MSR_TSC_AUX => hvm_cpu.msr_tsc_aux,
+            _ => return Err(XenDriverError::UnsupportedMsr(index).into()),
+        };
+        Ok(value)
+    }
+
+    fn write_msr(&self, vcpu: u16, index: u32, value: u64) -> Result<(), MicrovmiError> {
+        let (buffer, mut cpu, size) = self
+            .xc
+            .domain_hvm_getcontext(self.domid, vcpu)
+            .map_err(MicrovmiError::other)?;
+        match index {
+            MSR_IA32_SYSENTER_CS => cpu.sysenter_cs = value,
+            MSR_IA32_SYSENTER_ESP => cpu.sysenter_esp = value,
+            MSR_IA32_SYSENTER_EIP => cpu.sysenter_eip = value,
+            MSR_EFER => cpu.msr_efer = value,
+            MSR_STAR => cpu.msr_star = value,
+            MSR_LSTAR => cpu.msr_lstar = value,
+            MSR_CSTAR => cpu.msr_cstar = value,
+            MSR_SYSCALL_MASK => cpu.msr_syscall_mask = value,
+            MSR_SHADOW_GS_BASE => cpu.shadow_gs = value,
+            MSR_TSC_AUX => cpu.msr_tsc_aux = value,
+            _ => return Err(XenDriverError::UnsupportedMsr(index).into()),
+        }
+        self.xc
+            .domain_hvm_setcontext(
+                self.domid,
+                buffer,
+                size.try_into().map_err(XenDriverError::from)?,
+            )
+            .map_err(MicrovmiError::other)?;
+        Ok(())
+    }
+
+    fn get_page_access(&self, paddr: u64) -> Result<Access, MicrovmiError> {
+        let access = self
+            .xc
+            .get_mem_access(self.domid, paddr >> PAGE_SHIFT)
+            .map_err(XenDriverError::from)?;
+        Ok(access.into())
+    }
+
+    fn set_page_access(&self, paddr: u64, access: Access) -> Result<(), MicrovmiError> {
+        let xen_access: XenPageAccess = access.try_into().map_err(MicrovmiError::other)?;
+        Ok(self
+            .xc
+            .set_mem_access(self.domid, xen_access, paddr >> PAGE_SHIFT, 1)
+            .map_err(XenDriverError::from)?)
+    }
+
+    fn pause(&mut self) -> Result<(), MicrovmiError> {
         debug!("pause");
         Ok(self
             .xc
@@ -501,7 +982,7 @@ impl Introspectable for Xen {
             .map_err(XenDriverError::from)?)
     }
 
-    fn resume(&mut self) -> Result<(), Box<dyn Error>> {
+    fn resume(&mut self) -> Result<(), MicrovmiError> {
         debug!("resume");
         Ok(self
             .xc
@@ -1,5 +1,7 @@
 use crate::api::DriverType;
 use std::error::Error;
+use std::io;
+use std::num::TryFromIntError;
 
 #[derive(thiserror::Error, Debug)]
 pub enum MicrovmiError {
@@ -7,9 +9,84 @@ pub enum MicrovmiError {
     NoDriverAvailable,
     #[error("driver {0:?} has not been compiled")]
     DriverNotCompiled(DriverType),
-    #[error("{source}")]
-    Other {
-        #[from]
+    #[error("{0} is not supported by this driver")]
+    Unsupported(&'static str),
+    /// The address is outside of the guest memory, or is not mapped
+    #[error("invalid address {address:#X}: {source}")]
+    InvalidAddress {
+        address: u64,
         source: Box<dyn Error>,
     },
+    #[error("VM not found: {source}")]
+    VmNotFound { source: Box<dyn Error> },
+    #[error("permission denied: {source}")]
+    PermissionDenied { source: Box<dyn Error> },
+    #[error("operation timed out: {source}")]
+    Timeout { source: Box<dyn Error> },
+    /// Driver internal error
+    #[error("{source}")]
+    Other { source: Box<dyn Error> },
+}
+
+impl MicrovmiError {
+    /// Convert any driver error into a MicrovmiError
+    pub(crate) fn other<E: Into<Box<dyn Error>>>(error: E) -> Self {
+        MicrovmiError::from(error.into())
+    }
+}
+
+impl From<Box<dyn Error>> for MicrovmiError {
+    fn from(error: Box<dyn Error>) -> Self {
+        // keep the classification of errors which have been boxed on the way
+        let error = match error.downcast::<MicrovmiError>() {
+            Ok(microvmi_error) => return *microvmi_error,
+            Err(error) => error,
+        };
+        match error.downcast::<io::Error>() {
+            Ok(io_error) => MicrovmiError::from(*io_error),
+            Err(source) => MicrovmiError::Other { source },
+        }
+    }
+}
+
+impl From<io::Error> for MicrovmiError {
+    fn from(error: io::Error) -> Self {
+        let source = Box::new(error);
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => MicrovmiError::PermissionDenied { source },
+            io::ErrorKind::TimedOut => MicrovmiError::Timeout { source },
+            _ => MicrovmiError::Other { source },
+        }
+    }
+}
+
+impl From<TryFromIntError> for MicrovmiError {
+    fn from(error: TryFromIntError) -> Self {
+        MicrovmiError::Other {
+            source: Box::new(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxed_errors_keep_their_kind() {
+        let boxed: Box<dyn Error> = Box::new(MicrovmiError::Unsupported("pause"));
+        assert!(matches!(
+            MicrovmiError::from(boxed),
+            MicrovmiError::Unsupported("pause")
+        ));
+        let boxed: Box<dyn Error> = Box::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(
+            MicrovmiError::from(boxed),
+            MicrovmiError::PermissionDenied { .. }
+        ));
+        assert!(matches!(
+            MicrovmiError::other("driver failure"),
+            MicrovmiError::Other { .. }
+        ));
+    }
 }
@@ -21,10 +21,21 @@ use enum_iterator::IntoEnumIterator;
 use api::params::DriverInitParams;
 use api::DriverType;
 use api::Introspectable;
+#[cfg(feature = "crashdump")]
+use driver::crashdump::CrashDump;
+#[cfg(feature = "elfcore")]
+use driver::elfcore::ElfCore;
+#[cfg(feature = "file")]
+use driver::file::RawFile;
 #[cfg(feature = "kvm")]
 use driver::kvm::Kvm;
+#[cfg(feature = "lime")]
+use driver::lime::Lime;
 #[cfg(feature = "mflow")]
 use driver::memflow::Memflow;
+/// In-memory driver, to test VMI logic without a hypervisor
+#[cfg(feature = "mock")]
+pub use driver::mock::Mock;
 #[cfg(feature = "virtualbox")]
 use driver::virtualbox::VBox;
 #[cfg(feature = "xen")]
@@ -128,6 +139,16 @@ fn init_driver(
         DriverType::VirtualBox => Ok(Box::new(VBox::new(_init_params)?)),
         #[cfg(feature = "xen")]
         DriverType::Xen => Ok(Box::new(Xen::new(_init_params)?)),
+        #[cfg(feature = "file")]
+        DriverType::File => Ok(Box::new(RawFile::new(_init_params)?)),
+        #[cfg(feature = "elfcore")]
+        DriverType::ElfCore => Ok(Box::new(ElfCore::new(_init_params)?)),
+        #[cfg(feature = "lime")]
+        DriverType::LiME => Ok(Box::new(Lime::new(_init_params)?)),
+        #[cfg(feature = "crashdump")]
+        DriverType::CrashDump => Ok(Box::new(CrashDump::new(_init_params)?)),
+        #[cfg(feature = "mock")]
+        DriverType::Mock => Ok(Box::new(Mock::new(_init_params)?)),
         #[allow(unreachable_patterns)]
         _ => Err(MicrovmiError::DriverNotCompiled(driver_type)),
     }
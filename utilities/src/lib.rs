@@ -1,7 +1,8 @@
 /// This crate implements utilities and common code shared by libmicrovmi examples
 use clap::{Arg, ArgMatches};
 use microvmi::api::params::{
-    CommonInitParams, DriverInitParams, KVMInitParams, MemflowConnectorParams, MemflowInitParams,
+    CommonInitParams, DriverInitParams, FileInitParams, KVMInitParams, MemflowConnectorParams,
+    MemflowInitParams,
 };
 
 /// This trait allows to convert a struct to Clap's command line arguments
@@ -35,6 +36,15 @@ impl Clappable for DriverInitParams {
                 .long("memflow_connector_args")
                 .multiple(true)
                 .min_values(1),
+            // file
+            Arg::with_name("file_path")
+                .long("file_path")
+                .takes_value(true)
+                .help("Driver parameter (required for File, ElfCore, LiME, CrashDump): memory dump file path"),
+            Arg::with_name("file_writable")
+                .long("file_writable")
+                .takes_value(false)
+                .help("Driver parameter (optional for File): allow copy-on-write modifications"),
         ]
     }
 
@@ -57,10 +67,15 @@ impl Clappable for DriverInitParams {
                     }
                 }),
             });
+        let file = matches.value_of("file_path").map(|path| FileInitParams {
+            path: path.to_string(),
+            writable: matches.is_present("file_writable"),
+        });
         DriverInitParams {
             common,
             kvm,
             memflow,
+            file,
             ..Default::default()
         }
     }
@@ -70,7 +85,9 @@ impl Clappable for DriverInitParams {
 mod tests {
     use super::Clappable;
     use clap::App;
-    use microvmi::api::params::{DriverInitParams, KVMInitParams, MemflowConnectorParams};
+    use microvmi::api::params::{
+        DriverInitParams, FileInitParams, KVMInitParams, MemflowConnectorParams,
+    };
 
     #[test]
     fn test_common_vm_name() {
@@ -149,4 +166,21 @@ mod tests {
             params.memflow.unwrap().connector_args.unwrap()
         )
     }
+
+    // tests for file
+    #[test]
+    fn test_file_path() {
+        let cmdline = vec!["test", "--file_path=/tmp/windows10.raw", "--file_writable"];
+        let matches = App::new("test")
+            .args(DriverInitParams::to_clap_args().as_ref())
+            .get_matches_from(cmdline);
+        let params = DriverInitParams::from_matches(&matches);
+        assert_eq!(
+            FileInitParams {
+                path: String::from("/tmp/windows10.raw"),
+                writable: true,
+            },
+            params.file.unwrap()
+        )
+    }
 }